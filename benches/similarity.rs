@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use std::time::Instant;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use futures::executor::block_on;
+use detector::{Detector, chat_action::ChatAction, error::DetectorError, detector_params::DetectorParams, reg_date::RegDate, reg_date_loader::RegDateLoader};
+
+const AUTHOR: &str = "chatty_author";
+
+struct OldAccountLoader;
+
+#[async_trait]
+impl RegDateLoader for OldAccountLoader {
+    async fn load(&self, _author: &str) -> Result<Option<RegDate>, DetectorError> {
+        Ok(Some(RegDate::new(2010, 1, 1)))
+    }
+}
+
+struct MessageGenerator {
+    seed: u64,
+    index: u64,
+    words: Vec<String>
+}
+
+impl MessageGenerator {
+    fn new() -> Self {
+        let mut generator = MessageGenerator {
+            seed: 42,
+            index: 0,
+            words: Vec::new()
+        };
+
+        let letters: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
+        generator.words = (0..2000)
+            .map(|_| {
+                let length = 2 + generator.next() % 7;
+                (0..length)
+                    .map(|_| letters[(generator.next() % letters.len() as u64) as usize])
+                    .collect()
+            })
+            .collect();
+        generator
+    }
+
+    fn next_message(&mut self) -> ChatAction {
+        self.index += 1;
+        let length = 1 + self.next() % 7;
+        let content = (0..length)
+            .map(|_| {
+                let word = self.next() % self.words.len() as u64;
+                self.words[word as usize].clone()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        ChatAction::Message {
+            id: self.index.to_string(),
+            author: AUTHOR.to_string(),
+            timestamp: self.index * 10_000_000,
+            content,
+            badges: None,
+            context_params: String::new()
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.seed = self.seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.seed >> 33
+    }
+}
+
+fn detector_with_history(history: u64) -> (Detector, MessageGenerator) {
+    // Length and speed checks are disabled so that every message reaches the similarity check
+    // History limits are lifted to see how the lookup scales on its own
+    let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+    for field in ["avgTimeThreshold", "avgLengthMessageCount", "burstMessageCount", "copypastaAuthorCount", "authorIdleTimeout", "maxTrackedAuthors", "maxMessagesPerAuthor"] {
+        json[field] = 0.into();
+    }
+    let params: DetectorParams = serde_json::from_value(json).unwrap();
+    let mut detector = Detector::new(params, Box::new(OldAccountLoader));
+    let mut generator = MessageGenerator::new();
+
+    let actions = (0..history)
+        .map(|_| generator.next_message())
+        .collect();
+    block_on(detector.process_messages(actions));
+
+    (detector, generator)
+}
+
+/// Measures the cost of a single message sent by an author who already has `history` messages.
+/// Every measured message is new, so the history keeps growing during the measurement,
+/// but only by a fraction of its initial size for the larger inputs.
+fn similarity_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("similarity_lookup");
+    group.sample_size(10);
+
+    for history in [100, 1_000, 10_000] {
+        group.bench_with_input(BenchmarkId::from_parameter(history), &history, |b, &history| {
+            b.iter_custom(|iters| {
+                let (mut detector, mut generator) = detector_with_history(history);
+                let actions: Vec<ChatAction> = (0..iters)
+                    .map(|_| generator.next_message())
+                    .collect();
+
+                let start = Instant::now();
+                for action in actions {
+                    block_on(detector.process_messages(vec![action]));
+                }
+                start.elapsed()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, similarity_lookup);
+criterion_main!(benches);
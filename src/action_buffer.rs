@@ -0,0 +1,252 @@
+use std::collections::{HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+
+use super::{chat_action::ChatAction, detector_params::{DetectorParams, LateMessagePolicy}};
+
+/// Puts chat actions from different batches in chronological order before they reach `StreamData`,
+/// dropping duplicate messages and actions that arrived too late to be processed in order.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", from = "StoredActionBuffer")]
+pub struct ActionBuffer {
+    pending: Vec<ChatAction>,
+    newest_timestamp: u64,
+    watermark: u64,
+    #[serde(skip)]
+    seen_ids: HashSet<String>,
+    seen_ids_order: VecDeque<String>,
+    late_actions: u64,
+    duplicate_messages: u64,
+}
+
+/// `ActionBuffer` as it is stored in a `Snapshot`, the set of seen ids is rebuilt from their order.
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct StoredActionBuffer {
+    pending: Vec<ChatAction>,
+    newest_timestamp: u64,
+    watermark: u64,
+    seen_ids_order: VecDeque<String>,
+    late_actions: u64,
+    duplicate_messages: u64,
+}
+
+impl From<StoredActionBuffer> for ActionBuffer {
+    fn from(stored: StoredActionBuffer) -> Self {
+        ActionBuffer {
+            pending: stored.pending,
+            newest_timestamp: stored.newest_timestamp,
+            watermark: stored.watermark,
+            seen_ids: stored.seen_ids_order.iter().cloned().collect(),
+            seen_ids_order: stored.seen_ids_order,
+            late_actions: stored.late_actions,
+            duplicate_messages: stored.duplicate_messages,
+        }
+    }
+}
+
+impl Default for ActionBuffer {
+    fn default() -> Self {
+        ActionBuffer::new()
+    }
+}
+
+impl ActionBuffer {
+    pub fn new() -> Self {
+        ActionBuffer {
+            pending: Vec::new(),
+            newest_timestamp: 0,
+            watermark: 0,
+            seen_ids: HashSet::new(),
+            seen_ids_order: VecDeque::new(),
+            late_actions: 0,
+            duplicate_messages: 0,
+        }
+    }
+
+    /// Accepts a new batch and returns, in chronological order, every action that is ready to be processed.
+    pub fn push(&mut self, actions: Vec<ChatAction>, detector_params: &DetectorParams) -> Vec<ChatAction> {
+        let (policy, _) = detector_params.late_message_policy();
+        let (remembered_ids, _) = detector_params.remembered_message_ids();
+        for action in actions {
+            if let ChatAction::Message { id, .. } = &action {
+                if !self.remember_id(id, remembered_ids as usize) {
+                    self.duplicate_messages += 1;
+                    continue;
+                }
+            }
+
+            if policy != LateMessagePolicy::Process && action.timestamp() < self.watermark {
+                self.late_actions += 1;
+                continue;
+            }
+
+            self.newest_timestamp = self.newest_timestamp.max(action.timestamp());
+            self.pending.push(action);
+        }
+
+        match policy {
+            LateMessagePolicy::Process | LateMessagePolicy::Drop => self.flush(),
+            LateMessagePolicy::Reorder => {
+                let lateness = detector_params.max_message_lateness().0 as u64 * 1_000;
+                self.release_older_than(self.newest_timestamp.saturating_sub(lateness))
+            }
+        }
+    }
+
+    /// Returns every buffered action, regardless of how recent it is.
+    pub fn flush(&mut self) -> Vec<ChatAction> {
+        self.release_older_than(u64::MAX)
+    }
+
+    pub fn late_actions(&self) -> u64 {
+        self.late_actions
+    }
+
+    pub fn duplicate_messages(&self) -> u64 {
+        self.duplicate_messages
+    }
+
+    fn release_older_than(&mut self, cutoff: u64) -> Vec<ChatAction> {
+        self.pending.sort_by_key(|action| action.timestamp());
+        let ready_count = self.pending
+            .iter()
+            .take_while(|action| action.timestamp() <= cutoff)
+            .count();
+        let ready: Vec<ChatAction> = self.pending.drain(..ready_count).collect();
+
+        if let Some(action) = ready.last() {
+            self.watermark = self.watermark.max(action.timestamp());
+        }
+
+        ready
+    }
+
+    /// Returns false if the id is one of the last `remembered_ids` ones.
+    fn remember_id(&mut self, id: &str, remembered_ids: usize) -> bool {
+        if self.seen_ids.contains(id) {
+            return false;
+        }
+
+        self.seen_ids.insert(id.to_string());
+        self.seen_ids_order.push_back(id.to_string());
+        while self.seen_ids_order.len() > remembered_ids {
+            if let Some(oldest_id) = self.seen_ids_order.pop_front() {
+                self.seen_ids.remove(&oldest_id);
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use crate::{chat_action::ChatAction, detector_params::DetectorParams};
+    use super::ActionBuffer;
+
+    fn message(id: &str, timestamp: u64) -> ChatAction {
+        ChatAction::Message {
+            id: id.to_string(),
+            author: "author".to_string(),
+            timestamp,
+            content: String::new(),
+            badges: None,
+            context_params: String::new()
+        }
+    }
+
+    fn ids(actions: Vec<ChatAction>) -> Vec<String> {
+        actions
+            .into_iter()
+            .map(|action| match action {
+                ChatAction::Message { id, .. } => id,
+                _ => unreachable!()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_late_and_duplicate_actions() {
+        struct TestCase {
+            policy: &'static str,
+            expected_batches: Vec<Vec<&'static str>>,
+            expected_flush: Vec<&'static str>,
+            expected_late: u64,
+            expected_duplicate: u64
+        }
+
+        let batches = [
+            vec![("b", 2_000_000), ("a", 1_000_000)],
+            vec![("d", 4_000_000), ("c", 1_500_000), ("b", 2_000_000)],
+            vec![("e", 7_000_000), ("f", 3_500_000)],
+        ];
+
+        let test_cases = vec![
+            TestCase {
+                policy: "process",
+                expected_batches: vec![vec!["a", "b"], vec!["c", "d"], vec!["f", "e"]],
+                expected_flush: vec![],
+                expected_late: 0,
+                expected_duplicate: 1
+            },
+            TestCase {
+                policy: "drop",
+                expected_batches: vec![vec!["a", "b"], vec!["d"], vec!["e"]],
+                expected_flush: vec![],
+                expected_late: 2,
+                expected_duplicate: 1
+            },
+            TestCase {
+                policy: "reorder",
+                expected_batches: vec![vec![], vec!["a", "c", "b"], vec!["f", "d"]],
+                expected_flush: vec!["e"],
+                expected_late: 0,
+                expected_duplicate: 1
+            },
+        ];
+
+        for test_case in test_cases {
+            let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+            json["lateMessagePolicy"] = test_case.policy.into();
+            json["maxMessageLateness"] = 2000.into();
+            let params: DetectorParams = serde_json::from_value(json).unwrap();
+
+            let mut buffer = ActionBuffer::new();
+            for (batch, expected) in batches.iter().zip(test_case.expected_batches) {
+                let batch = batch.iter().map(|(id, timestamp)| message(id, *timestamp)).collect();
+                assert_eq!(expected, ids(buffer.push(batch, &params)), "policy: {}", test_case.policy);
+            }
+
+            assert_eq!(test_case.expected_flush, ids(buffer.flush()), "policy: {}", test_case.policy);
+            assert_eq!(test_case.expected_late, buffer.late_actions(), "policy: {}", test_case.policy);
+            assert_eq!(test_case.expected_duplicate, buffer.duplicate_messages(), "policy: {}", test_case.policy);
+        }
+
+        // Only the most recent ids are remembered
+        let params = DetectorParams::builder().remembered_message_ids(1).build().unwrap();
+        let mut buffer = ActionBuffer::new();
+        let batch = vec![message("a", 1_000_000), message("b", 2_000_000), message("a", 3_000_000), message("a", 4_000_000)];
+        assert_eq!(vec!["a", "b", "a"], ids(buffer.push(batch, &params)));
+        assert_eq!(1, buffer.duplicate_messages());
+    }
+
+    #[test]
+    pub fn test_buffer_round_trip() {
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        json["lateMessagePolicy"] = "reorder".into();
+        json["maxMessageLateness"] = 2000.into();
+        let params: DetectorParams = serde_json::from_value(json).unwrap();
+
+        let mut buffer = ActionBuffer::new();
+        assert_eq!(vec!["a"], ids(buffer.push(vec![message("a", 1_000_000), message("b", 4_000_000)], &params)));
+
+        let json = serde_json::to_string(&buffer).unwrap();
+        let mut restored: ActionBuffer = serde_json::from_str(&json).unwrap();
+
+        // Duplicates and late actions are still filtered out, held back actions are released in order
+        assert_eq!(vec!["c", "b"], ids(restored.push(vec![message("a", 1_000_000), message("late", 500_000), message("c", 3_000_000), message("d", 6_000_000)], &params)));
+        assert_eq!(1, restored.duplicate_messages());
+        assert_eq!(1, restored.late_actions());
+    }
+}
@@ -1,91 +1,217 @@
-use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::mem::size_of;
+use serde::{Deserialize, Serialize};
 
-use super::{detector_params::DetectorParams, message_data::MessageData};
+use super::{chat_action::Badges, check::AuthorState, detector_params::DetectorParams, rolling_average::RollingAverage, similarity_index::SimilarityIndex};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub enum Reason {
     SlowMode,
     TooFast(u32),
+    Burst(u32),
     TooLong(f32),
     Similar,
-    RetractedMessage
+    RetractedMessage,
+    Copypasta(u32),
+    YoungAccount,
+    /// Author is on the deny list
+    Denylisted,
+    /// Reported by a `Check` implemented outside of this crate
+    Custom {
+        name: String,
+        weight: u32
+    }
 }
 
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AuthorData {
     last_message_timestamp: u64,
-    sent_messages: Vec<MessageData>,
+    sent_messages: SimilarityIndex,
     avg_delay: u32,
     total_messages: u32,
     avg_message_length: f32,
+    recent_delays: RollingAverage,
+    recent_lengths: RollingAverage,
+    recent_timestamps: VecDeque<u64>,
+    retracted_messages: u32,
+    #[serde(default)]
+    last_delay: u32,
+    #[serde(default)]
+    last_message: String,
+    #[serde(default)]
+    badges: Vec<Badges>,
+    /// Similar messages counted for the last message, 0 if it was too short to be compared
+    #[serde(default)]
+    last_similar_count: u32,
+    /// Time of the author's last message, including the ones sent after they were reported
+    #[serde(default)]
+    last_seen_timestamp: u64,
 }
 
 impl AuthorData {
-    pub fn new(first_message: String, message_timestamp: u64) -> Self {
+    pub fn new(first_message: String, message_timestamp: u64, detector_params: &DetectorParams) -> Self {
         let message_length = first_message.chars().count();
-        let new_message_data = MessageData::new(first_message);
+        let mut sent_messages = SimilarityIndex::new();
+        let last_message = first_message.clone();
+        let similar_count = sent_messages.add_message(&first_message, detector_params);
+        let last_similar_count = if detector_params.should_check_message(message_length as u32) {
+            similar_count
+        } else {
+            0
+        };
+        let mut recent_lengths = RollingAverage::new();
+        recent_lengths.add(message_length as f32, detector_params.averaging_window_size());
+        let mut recent_timestamps = VecDeque::new();
+        recent_timestamps.push_back(message_timestamp);
         AuthorData {
             last_message_timestamp: message_timestamp,
-            sent_messages: vec![new_message_data],
+            sent_messages,
             avg_delay: 0,
             total_messages: 1,
             avg_message_length: message_length as f32,
+            recent_delays: RollingAverage::new(),
+            recent_lengths,
+            recent_timestamps,
+            retracted_messages: 0,
+            last_delay: 0,
+            last_message,
+            badges: Vec::new(),
+            last_similar_count,
+            last_seen_timestamp: message_timestamp,
         }
     }
 
-    pub fn check_message(
+    /// Updates author's statistics with the new message and returns them for the checks to inspect.
+    pub fn record_message(
         &mut self, 
         timestamp: u64, 
-        content: Cow<str>,
+        content: &str,
         current_slow_mode: u32,
         detector_params: &DetectorParams,
-    ) -> Option<Reason> {
+    ) -> AuthorState {
         self.total_messages += 1;
 
-        let time_diff = (timestamp - self.last_message_timestamp) as u32;
+        let time_diff = timestamp.saturating_sub(self.last_message_timestamp) as u32;
         self.last_message_timestamp = timestamp;
-        let broke_slow_mode = 
-            current_slow_mode != 0 && 
-            time_diff < current_slow_mode;
-        
-        if broke_slow_mode {
-            return Some(Reason::SlowMode);
+        self.last_seen_timestamp = self.last_seen_timestamp.max(timestamp);
+        self.last_delay = time_diff;
+
+        self.recent_timestamps.push_back(timestamp);
+        while self.recent_timestamps.len() > detector_params.burst_message_count_usize().max(1) {
+            self.recent_timestamps.pop_front();
         }
 
-        let total_messages = self.total_messages as u32;
+        let total_messages = self.total_messages;
+        let averaging_window = detector_params.averaging_window_size();
+
         self.avg_delay = (time_diff + (total_messages - 1) * self.avg_delay) / total_messages;
-        if detector_params.is_too_fast(self.avg_delay, self.total_messages) {
-            return Some(Reason::TooFast(self.avg_delay));
-        }
+        self.recent_delays.add(time_diff as f32, averaging_window);
 
-        self.avg_message_length = (content.chars().count() as f32 + (total_messages - 1) as f32 * self.avg_message_length) / total_messages as f32;
-        if detector_params.messages_are_too_long(self.avg_message_length, self.total_messages) {
-            return Some(Reason::TooLong(self.avg_message_length));
+        let message_length = content.chars().count() as f32;
+        self.avg_message_length = (message_length + (total_messages - 1) as f32 * self.avg_message_length) / total_messages as f32;
+        self.recent_lengths.add(message_length, averaging_window);
+
+        let similar_messages_count = if detector_params.should_check_message(message_length as u32) {
+            Some(self.sent_messages.add_message(content, detector_params))
+        } else {
+            None
+        };
+        self.last_message = content.to_string();
+        self.last_similar_count = similar_messages_count.unwrap_or(0);
+
+        AuthorState {
+            similar_messages_count,
+            ..self.current_state(current_slow_mode, detector_params)
         }
+    }
 
-        if !detector_params.should_check_message(content.chars().count() as u32) {
-            return None;
+    /// Author's statistics as of their last message, as seen with `detector_params`.
+    /// Similar messages are the ones counted for the last message when it was sent.
+    pub fn current_state(&self, current_slow_mode: u32, detector_params: &DetectorParams) -> AuthorState {
+        let average_delay = detector_params.current_average(
+            self.avg_delay as f32,
+            self.recent_delays.window_average(),
+            self.recent_delays.exponential_average()
+        ) as u32;
+        let average_message_length = detector_params.current_average(
+            self.avg_message_length,
+            self.recent_lengths.window_average(),
+            self.recent_lengths.exponential_average()
+        );
+
+        let recent_messages = self.recent_timestamps.len().min(detector_params.burst_message_count_usize().max(1));
+        let recent_messages_time_span = match self.recent_timestamps.iter().rev().nth(recent_messages.saturating_sub(1)) {
+            Some(oldest) => self.last_message_timestamp - oldest,
+            None => 0
+        };
+
+        AuthorState {
+            total_messages: self.total_messages,
+            time_since_last_message: self.last_delay,
+            slow_mode: current_slow_mode,
+            average_delay,
+            average_message_length,
+            recent_messages: recent_messages as u32,
+            recent_messages_time_span,
+            similar_messages_count: Some(self.last_similar_count).filter(|count| *count != 0),
+            retracted_messages: self.retracted_messages,
         }
+    }
 
-        let mut found_similar_message = false;
+    pub fn last_message(&self) -> &str {
+        &self.last_message
+    }
 
-        for message_data in self.sent_messages.iter_mut() {
-            if message_data.is_message_similar(&content) {
-                found_similar_message = true;
-                message_data.reconstruct_message(&content);
+    /// Author's badges as of their last message.
+    pub fn badges(&self) -> &[Badges] {
+        &self.badges
+    }
 
-                if detector_params.sent_too_many_similar_messages(message_data.count()) {
-                    return Some(Reason::Similar);
-                }
+    pub fn set_badges(&mut self, badges: Vec<Badges>) {
+        self.badges = badges;
+    }
 
-                break;
-            }
-        }
+    pub fn last_message_timestamp(&self) -> u64 {
+        self.last_message_timestamp
+    }
+
+    pub fn last_seen_timestamp(&self) -> u64 {
+        self.last_seen_timestamp.max(self.last_message_timestamp)
+    }
+
+    /// Registers a message that isn't recorded, so that the author doesn't look idle.
+    pub fn mark_seen(&mut self, timestamp: u64) {
+        self.last_seen_timestamp = self.last_seen_timestamp.max(timestamp);
+    }
+
+    pub fn stored_messages(&self) -> usize {
+        self.sent_messages.len()
+    }
 
-        if !found_similar_message {
-            let new_message_data = MessageData::new(content.to_string());
-            self.sent_messages.push(new_message_data);
+    pub fn estimated_size(&self) -> usize {
+        size_of::<AuthorData>() + self.sent_messages.estimated_size()
+    }
+
+    /// Registers that the author retracted a message at `timestamp`.
+    /// Since retractions don't say which message was removed, the author's
+    /// last message is assumed to be the retracted one.
+    pub fn check_retraction(&mut self, timestamp: u64, detector_params: &DetectorParams) -> Option<Reason> {
+        let time_diff = timestamp.saturating_sub(self.last_message_timestamp) as u32;
+        if !detector_params.retracted_too_quickly(time_diff) {
+            return None;
         }
 
-        None
+        self.retracted_messages += 1;
+        self.retraction_reason(detector_params)
     }
-}
\ No newline at end of file
+
+    pub fn retraction_reason(&self, detector_params: &DetectorParams) -> Option<Reason> {
+        if detector_params.retracted_too_many_messages(self.retracted_messages) {
+            Some(Reason::RetractedMessage)
+        } else {
+            None
+        }
+    }
+}
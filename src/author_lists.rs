@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::error::DetectorError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ListKind {
+    /// Author's actions are never checked
+    Allow,
+    /// Every message of the author is reported with `Reason::Denylisted`
+    Deny
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListEntry {
+    pub author: String,
+    pub list: ListKind,
+    /// Timestamp in the same units as `ChatAction` timestamps, `None` if the entry never expires
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Why the author was added, for other moderators
+    #[serde(default)]
+    pub note: String
+}
+
+impl ListEntry {
+    pub fn is_expired(&self, timestamp: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| timestamp > expires_at)
+    }
+}
+
+/// Authors that moderators trust or know to be spammers.
+/// An author is on one list at most, adding them to a list removes them from the other one.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(from = "Vec<ListEntry>", into = "Vec<ListEntry>")]
+pub struct AuthorLists {
+    entries: HashMap<String, ListEntry>
+}
+
+impl AuthorLists {
+    pub fn new() -> Self {
+        AuthorLists::default()
+    }
+
+    /// Returns the entry the author had before.
+    pub fn insert(&mut self, entry: ListEntry) -> Option<ListEntry> {
+        self.entries.insert(entry.author.clone(), entry)
+    }
+
+    pub fn remove(&mut self, author: &str) -> Option<ListEntry> {
+        self.entries.remove(author)
+    }
+
+    pub fn get(&self, author: &str) -> Option<&ListEntry> {
+        self.entries.get(author)
+    }
+
+    /// The list the author is on at `timestamp`.
+    pub fn status(&self, author: &str, timestamp: u64) -> Option<ListKind> {
+        self.entries
+            .get(author)
+            .filter(|entry| !entry.is_expired(timestamp))
+            .map(|entry| entry.list)
+    }
+
+    /// Every entry, sorted by author.
+    pub fn entries(&self) -> Vec<ListEntry> {
+        let mut entries: Vec<ListEntry> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| a.author.cmp(&b.author));
+        entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forgets the entries that expired by `timestamp` and returns them.
+    pub fn remove_expired(&mut self, timestamp: u64) -> Vec<ListEntry> {
+        let expired: Vec<String> = self.entries
+            .values()
+            .filter(|entry| entry.is_expired(timestamp))
+            .map(|entry| entry.author.clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|author| self.entries.remove(author))
+            .collect()
+    }
+
+    /// A JSON array of entries, sorted by author.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("author lists are always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<AuthorLists, DetectorError> {
+        let lists: AuthorLists = serde_json::from_str(json)
+            .map_err(|error| DetectorError::InvalidAuthorLists(error.to_string()))?;
+        if lists.entries.keys().any(|author| author.is_empty()) {
+            return Err(DetectorError::InvalidAuthorLists("entry without author".to_string()));
+        }
+        Ok(lists)
+    }
+}
+
+impl From<Vec<ListEntry>> for AuthorLists {
+    /// When an author appears several times, the last entry wins.
+    fn from(entries: Vec<ListEntry>) -> Self {
+        AuthorLists {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.author.clone(), entry))
+                .collect()
+        }
+    }
+}
+
+impl From<AuthorLists> for Vec<ListEntry> {
+    fn from(lists: AuthorLists) -> Self {
+        lists.entries()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use crate::error::DetectorError;
+    use super::{AuthorLists, ListEntry, ListKind};
+
+    fn entry(author: &str, list: ListKind, expires_at: Option<u64>) -> ListEntry {
+        ListEntry { author: author.to_string(), list, expires_at, note: String::new() }
+    }
+
+    #[test]
+    pub fn test_author_lists() {
+        struct TestCase {
+            author: &'static str,
+            timestamp: u64,
+            expected_status: Option<ListKind>
+        }
+
+        let mut lists = AuthorLists::new();
+        lists.insert(entry("regular", ListKind::Allow, None));
+        lists.insert(entry("bot", ListKind::Deny, Some(1_000)));
+        lists.insert(entry("switched", ListKind::Allow, None));
+        let previous = lists.insert(entry("switched", ListKind::Deny, None));
+        assert_eq!(Some(ListKind::Allow), previous.map(|entry| entry.list));
+
+        let test_cases = vec![
+            TestCase { author: "regular", timestamp: u64::MAX, expected_status: Some(ListKind::Allow) },
+            TestCase { author: "bot", timestamp: 1_000, expected_status: Some(ListKind::Deny) },
+            TestCase { author: "bot", timestamp: 1_001, expected_status: None },
+            TestCase { author: "switched", timestamp: 0, expected_status: Some(ListKind::Deny) },
+            TestCase { author: "stranger", timestamp: 0, expected_status: None },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected_status, lists.status(test_case.author, test_case.timestamp), "author: {} at {}", test_case.author, test_case.timestamp);
+        }
+
+        // Lists survive the round trip through JSON
+        let json = lists.to_json();
+        assert_eq!(lists, AuthorLists::from_json(&json).unwrap());
+        let imported = AuthorLists::from_json(r#"[{"author": "spammer", "list": "deny"}]"#).unwrap();
+        assert_eq!(vec![entry("spammer", ListKind::Deny, None)], imported.entries());
+        assert!(matches!(AuthorLists::from_json(r#"[{"author": "spammer", "list": "ban"}]"#), Err(DetectorError::InvalidAuthorLists(_))));
+        assert!(matches!(AuthorLists::from_json(r#"[{"author": "", "list": "deny"}]"#), Err(DetectorError::InvalidAuthorLists(_))));
+
+        let expired = lists.remove_expired(2_000);
+        assert_eq!(vec!["bot".to_string()], expired.into_iter().map(|entry| entry.author).collect::<Vec<_>>());
+        assert_eq!(2, lists.len());
+    }
+}
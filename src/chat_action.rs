@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum Badges {
     MEMBER,
     VERIFIED,
@@ -8,7 +8,7 @@ pub enum Badges {
     MODERATOR
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum ChatAction {
     Message {
         id: String,
@@ -21,9 +21,25 @@ pub enum ChatAction {
     Support {
         author: String,
         timestamp: u64,
+        /// In cents or other minor units of `currency`
+        #[serde(default)]
+        amount: u64,
+        /// ISO 4217 code, e.g. `USD`
+        #[serde(default)]
+        currency: String,
     },
     RetractedMessage {
         author: String,
         timestamp: u64,
     }
-}
\ No newline at end of file
+}
+
+impl ChatAction {
+    pub fn timestamp(&self) -> u64 {
+        match self {
+            ChatAction::Message { timestamp, .. } => *timestamp,
+            ChatAction::Support { timestamp, .. } => *timestamp,
+            ChatAction::RetractedMessage { timestamp, .. } => *timestamp
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{author_data::Reason, detector_params::DetectorParams};
+
+/// Author's statistics, updated with the message that is being checked.
+#[derive(Debug, Clone)]
+pub struct AuthorState {
+    pub total_messages: u32,
+    /// Milliseconds since author's previous message
+    pub time_since_last_message: u32,
+    /// Current slow mode delay in milliseconds, 0 if slow mode is off
+    pub slow_mode: u32,
+    /// Average delay between messages, according to `AveragingMode`
+    pub average_delay: u32,
+    /// Average message length, according to `AveragingMode`
+    pub average_message_length: f32,
+    /// Number of messages that are tracked for the burst rule, including this one
+    pub recent_messages: u32,
+    /// Milliseconds between the oldest and the newest of `recent_messages`
+    pub recent_messages_time_span: u64,
+    /// How many similar messages the author sent, or `None` if the message is too short to compare
+    pub similar_messages_count: Option<u32>,
+    pub retracted_messages: u32,
+}
+
+/// A rule that is run against every message of an author that isn't reported yet.
+/// Each broken rule contributes its weight to the author's `SpamScore`.
+pub trait Check: Send + Sync {
+    fn check(&self, author: &AuthorState, message: &str, detector_params: &DetectorParams) -> Option<Reason>;
+}
+
+pub struct SlowModeCheck;
+
+impl Check for SlowModeCheck {
+    fn check(&self, author: &AuthorState, _message: &str, _detector_params: &DetectorParams) -> Option<Reason> {
+        let broke_slow_mode = 
+            author.slow_mode != 0 && 
+            author.time_since_last_message < author.slow_mode;
+
+        if broke_slow_mode {
+            Some(Reason::SlowMode)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct BurstCheck;
+
+impl Check for BurstCheck {
+    fn check(&self, author: &AuthorState, _message: &str, detector_params: &DetectorParams) -> Option<Reason> {
+        if detector_params.is_burst(author.recent_messages as usize, author.recent_messages_time_span) {
+            Some(Reason::Burst(author.recent_messages))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct TooFastCheck;
+
+impl Check for TooFastCheck {
+    fn check(&self, author: &AuthorState, _message: &str, detector_params: &DetectorParams) -> Option<Reason> {
+        if detector_params.is_too_fast(author.average_delay, author.total_messages) {
+            Some(Reason::TooFast(author.average_delay))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct TooLongCheck;
+
+impl Check for TooLongCheck {
+    fn check(&self, author: &AuthorState, _message: &str, detector_params: &DetectorParams) -> Option<Reason> {
+        if detector_params.messages_are_too_long(author.average_message_length, author.total_messages) {
+            Some(Reason::TooLong(author.average_message_length))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct SimilarityCheck;
+
+impl Check for SimilarityCheck {
+    fn check(&self, author: &AuthorState, _message: &str, detector_params: &DetectorParams) -> Option<Reason> {
+        match author.similar_messages_count {
+            Some(count) if detector_params.sent_too_many_similar_messages(count) => Some(Reason::Similar),
+            _ => None
+        }
+    }
+}
+
+/// Built-in rules in the order they used to be checked in.
+pub fn default_checks() -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(SlowModeCheck),
+        Box::new(BurstCheck),
+        Box::new(TooFastCheck),
+        Box::new(TooLongCheck),
+        Box::new(SimilarityCheck),
+    ]
+}
+
+pub fn run_checks(
+    checks: &[Box<dyn Check>],
+    author: &AuthorState,
+    message: &str,
+    detector_params: &DetectorParams
+) -> Vec<Reason> {
+    checks
+        .iter()
+        .filter_map(|check| check.check(author, message, detector_params))
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use crate::{author_data::{AuthorData, Reason}, detector_params::DetectorParams};
+    use super::{AuthorState, Check, default_checks, run_checks};
+
+    fn params(averaging_mode: &str, burst_message_count: u32) -> DetectorParams {
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        json["averagingMode"] = averaging_mode.into();
+        json["averagingWindow"] = 3.into();
+        json["burstMessageCount"] = burst_message_count.into();
+        json["burstTimeWindow"] = 3000.into();
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    pub fn test_flooding_after_calm_period() {
+        struct TestCase {
+            params: DetectorParams,
+            expected: Option<&'static str>
+        }
+
+        let test_cases = vec![
+            TestCase { params: params("cumulative", 0), expected: None },
+            TestCase { params: params("slidingWindow", 0), expected: Some("TooFast") },
+            TestCase { params: params("exponential", 0), expected: Some("TooFast") },
+            TestCase { params: params("cumulative", 4), expected: Some("Burst") },
+        ];
+
+        let checks = default_checks();
+
+        for test_case in test_cases {
+            // An hour of calm chatting, one message per minute, followed by a flood
+            let mut timestamp = 0;
+            let mut author_data = AuthorData::new("hello".to_string(), timestamp, &test_case.params);
+            let mut reason = None;
+            for i in 0..60u64 {
+                timestamp += 60_000;
+                let message = format!("msg {}", i);
+                let state = author_data.record_message(timestamp, &message, 0, &test_case.params);
+                reason = reason.or(run_checks(&checks, &state, &message, &test_case.params).into_iter().next());
+            }
+            assert!(reason.is_none());
+
+            for i in 0..7u64 {
+                timestamp += 700;
+                let message = format!("spam {}", i);
+                let state = author_data.record_message(timestamp, &message, 0, &test_case.params);
+                reason = reason.or(run_checks(&checks, &state, &message, &test_case.params).into_iter().next());
+            }
+
+            let reason = reason.map(|reason| match reason {
+                Reason::TooFast(_) => "TooFast",
+                Reason::Burst(_) => "Burst",
+                _ => "Other"
+            });
+            assert_eq!(test_case.expected, reason);
+        }
+    }
+
+    struct LinkCheck;
+
+    impl Check for LinkCheck {
+        fn check(&self, _author: &AuthorState, message: &str, _detector_params: &DetectorParams) -> Option<Reason> {
+            if message.contains("http") {
+                Some(Reason::Custom { name: "link".to_string(), weight: 100 })
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_custom_checks() {
+        let params = DetectorParams::get_default();
+        let mut checks = default_checks();
+        checks.push(Box::new(LinkCheck));
+
+        let mut author_data = AuthorData::new("hello".to_string(), 0, &params);
+        let message = "see http://example.com";
+        let state = author_data.record_message(60_000, message, 0, &params);
+        let reasons = run_checks(&checks, &state, message, &params);
+
+        assert_eq!(1, reasons.len());
+        assert!(matches!(&reasons[0], Reason::Custom { name, weight: 100 } if name == "link"));
+        assert_eq!(100, params.weight(&reasons[0]));
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use serde_json::{Map, Value};
+use toml::Spanned;
+
+use crate::{detector_params::{DetectorParams, ParamViolation, TextFieldDescriptor}, error::DetectorError, preset::Preset, reg_date::RegDate, schema::{FieldDescriptor, FieldKind}};
+
+pub const DEFAULT_ENV_PREFIX: &str = "DETECTOR_";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json
+}
+
+impl ConfigFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<ConfigFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None
+        }
+    }
+}
+
+/// Where a value of the config came from, used to point at the exact place of an error.
+enum Origin {
+    File {
+        line: usize,
+        column: usize
+    },
+    Env(String)
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    origin: Origin
+}
+
+/// Reads `DetectorParams` from a TOML or JSON file, e.g.
+///
+/// ```toml
+/// preset = "strict"
+/// ban_score = 300
+/// min_reg_date = 2020-01-01
+/// ```
+///
+/// `preset` is optional and defaults to `Preset::Default`, every other key replaces a field of the preset.
+/// Fields can be written as they are serialized (`banScore`) or in snake case (`ban_score`).
+/// Environment variables such as `DETECTOR_BAN_SCORE` or `DETECTOR_PRESET` take precedence over the file.
+pub struct ConfigLoader {
+    env_prefix: String,
+    env: HashMap<String, String>
+}
+
+impl ConfigLoader {
+    /// Loader that reads overrides from the environment of the current process.
+    /// Variables whose name or value isn't valid Unicode are ignored.
+    pub fn new() -> Self {
+        let env = std::env::vars_os()
+            .filter_map(|(variable, value)| Some((variable.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        ConfigLoader {
+            env_prefix: DEFAULT_ENV_PREFIX.to_string(),
+            env
+        }
+    }
+
+    pub fn with_env_prefix(mut self, env_prefix: &str) -> Self {
+        self.env_prefix = env_prefix.to_string();
+        self
+    }
+
+    /// Replaces the environment of the current process with `env`.
+    pub fn with_env<I: IntoIterator<Item = (String, String)>>(mut self, env: I) -> Self {
+        self.env = env.into_iter().collect();
+        self
+    }
+
+    pub fn load(&self, path: &Path) -> Result<DetectorParams, DetectorError> {
+        let format = ConfigFormat::from_path(path)
+            .ok_or_else(|| DetectorError::ConfigFailure(format!("{}: expected a .toml or .json file", path.display())))?;
+        let content = fs::read_to_string(path)
+            .map_err(|error| DetectorError::ConfigFailure(format!("{}: {}", path.display(), error)))?;
+        self.parse(&content, format, &path.display().to_string())
+    }
+
+    /// Same as `load`, with `source_name` used in place of the file name in errors.
+    pub fn parse(&self, content: &str, format: ConfigFormat, source_name: &str) -> Result<DetectorParams, DetectorError> {
+        let mut entries = match format {
+            ConfigFormat::Toml => toml_entries(content, source_name)?,
+            ConfigFormat::Json => json_entries(content, source_name)?
+        };
+        // Both formats come back sorted by key, errors are easier to follow in the order of the file
+        entries.sort_by_key(|entry| match entry.origin {
+            Origin::File { line, column } => (line, column),
+            Origin::Env(_) => (usize::MAX, 0)
+        });
+        entries.extend(self.env_entries());
+
+        let mut preset = Preset::Default;
+        let mut fields = Map::new();
+        let mut errors = Vec::new();
+        let descriptors = DetectorParams::descriptors();
+
+        for entry in entries {
+            if entry.key == "preset" {
+                match serde_json::from_value(entry.value) {
+                    Ok(value) => preset = value,
+                    Err(error) => errors.push(format!("{}: preset: {}", describe(&entry.origin, source_name), error))
+                }
+                continue;
+            }
+
+            match find_descriptor(&descriptors, &entry.key) {
+                Some(descriptor) => {
+                    // Fields are checked one by one, so every error can point at its own field.
+                    // Rules between fields depend on the preset and the other fields, so they are checked once everything is read.
+                    if let Err(message) = check_field(descriptor, &entry.value) {
+                        errors.push(format!("{}: {}: {}", describe(&entry.origin, source_name), entry.key, message));
+                    }
+                    fields.insert(descriptor.key.clone(), entry.value);
+                },
+                None => errors.push(format!("{}: unknown field {}", describe(&entry.origin, source_name), entry.key))
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(DetectorError::InvalidParams(errors.join("; ")));
+        }
+
+        preset
+            .params()
+            .with_overrides(&fields)
+            .map_err(|error| DetectorError::InvalidParams(format!("{}: {}", source_name, error_message(error))))
+    }
+
+    fn env_entries(&self) -> Vec<Entry> {
+        let preset = Some(("preset".to_string(), FieldKind::Select { options: Vec::new() }));
+        let fields = DetectorParams::descriptors()
+            .into_iter()
+            .map(|descriptor| (descriptor.name, descriptor.kind));
+
+        preset
+            .into_iter()
+            .chain(fields)
+            .filter_map(|(name, kind)| {
+                let variable = format!("{}{}", self.env_prefix, name.to_ascii_uppercase());
+                let value = self.env.get(&variable)?;
+                let value = match kind {
+                    FieldKind::Integer { .. } => value.trim().parse::<u64>().map(Value::from).unwrap_or_else(|_| Value::String(value.clone())),
+                    FieldKind::Select { .. } | FieldKind::Date | FieldKind::Currency => Value::String(value.clone())
+                };
+                Some(Entry { key: name, value, origin: Origin::Env(variable) })
+            })
+            .collect()
+    }
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        ConfigLoader::new()
+    }
+}
+
+impl DetectorParams {
+    /// Shorthand for `ConfigLoader::new().load(path)`.
+    pub fn from_config_file(path: &Path) -> Result<DetectorParams, DetectorError> {
+        ConfigLoader::new().load(path)
+    }
+}
+
+fn toml_entries(content: &str, source_name: &str) -> Result<Vec<Entry>, DetectorError> {
+    let table: BTreeMap<String, Spanned<toml::Value>> = toml::from_str(content).map_err(|error| {
+        let location = match error.span() {
+            Some(span) => at(source_name, content, span.start),
+            None => source_name.to_string()
+        };
+        DetectorError::InvalidParams(format!("{}: {}", location, error.message().trim()))
+    })?;
+
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| {
+            let (line, column) = line_column(content, value.span().start);
+            Entry { key, value: toml_to_json(value.into_inner()), origin: Origin::File { line, column } }
+        })
+        .collect())
+}
+
+fn json_entries(content: &str, source_name: &str) -> Result<Vec<Entry>, DetectorError> {
+    let object: Map<String, Value> = serde_json::from_str(content).map_err(|error| {
+        let message = error.to_string();
+        let message = message.split(" at line ").next().unwrap_or_default();
+        DetectorError::InvalidParams(format!("{}:{}:{}: {}", source_name, error.line(), error.column(), message))
+    })?;
+
+    // serde_json doesn't keep positions, so they are found by walking the top-level object
+    let offsets = json_value_offsets(content);
+    Ok(object
+        .into_iter()
+        .map(|(key, value)| {
+            let offset = offsets.get(&key).copied().unwrap_or_default();
+            let (line, column) = line_column(content, offset);
+            Entry { key, value, origin: Origin::File { line, column } }
+        })
+        .collect())
+}
+
+/// Byte offsets of the values of the top-level keys, `content` must be a valid JSON object.
+/// When a key is repeated, the last value wins, as it does in serde_json.
+fn json_value_offsets(content: &str) -> HashMap<String, usize> {
+    let bytes = content.as_bytes();
+    let mut offsets = HashMap::new();
+    let mut depth = 0;
+    let mut expecting_key = false;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'{' | b'[' => {
+                depth += 1;
+                expecting_key = depth == 1;
+            },
+            b'}' | b']' => depth -= 1,
+            b',' => expecting_key = depth == 1,
+            b'"' => {
+                let end = string_end(bytes, index);
+                if expecting_key {
+                    let value_start = skip_whitespace(bytes, end + 1) + 1;
+                    if let Ok(key) = serde_json::from_str::<String>(&content[index..=end]) {
+                        offsets.insert(key, skip_whitespace(bytes, value_start));
+                    }
+                    expecting_key = false;
+                }
+                index = end;
+            },
+            _ => {}
+        }
+        index += 1;
+    }
+
+    offsets
+}
+
+/// Index of the quote that closes the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut index = start + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'"' => return index,
+            _ => index += 1
+        }
+    }
+    bytes.len() - 1
+}
+
+fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+    let mut index = start;
+    while index < bytes.len() && bytes[index].is_ascii_whitespace() {
+        index += 1;
+    }
+    index
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::String(value),
+        toml::Value::Integer(value) => Value::from(value),
+        toml::Value::Float(value) => Value::from(value),
+        toml::Value::Boolean(value) => Value::Bool(value),
+        toml::Value::Datetime(value) => Value::String(value.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect())
+    }
+}
+
+/// Checks the value against its own field alone.
+fn check_field(descriptor: &FieldDescriptor, value: &Value) -> Result<(), String> {
+    match &descriptor.kind {
+        FieldKind::Integer { min, max, step: _, zero_disables } => {
+            let value: u32 = serde_json::from_value(value.clone()).map_err(|error| error.to_string())?;
+            let text_descriptor = TextFieldDescriptor {
+                name: descriptor.name.clone(),
+                min: *min,
+                max: *max,
+                step: 1,
+                zero_disables: *zero_disables
+            };
+            if text_descriptor.accepts(value) {
+                Ok(())
+            } else {
+                Err(ParamViolation { field: descriptor.name.clone(), value, min: *min, max: *max }.to_string())
+            }
+        },
+        FieldKind::Select { options } => {
+            let value: String = serde_json::from_value(value.clone()).map_err(|error| error.to_string())?;
+            if options.contains(&value) {
+                Ok(())
+            } else {
+                let expected: Vec<String> = options.iter().map(|option| format!("`{}`", option)).collect();
+                Err(format!("unknown variant `{}`, expected one of {}", value, expected.join(", ")))
+            }
+        },
+        FieldKind::Date => serde_json::from_value::<RegDate>(value.clone()).map(|_| ()).map_err(|error| error.to_string()),
+        FieldKind::Currency => serde_json::from_value::<String>(value.clone()).map(|_| ()).map_err(|error| error.to_string())
+    }
+}
+
+fn find_descriptor<'a>(descriptors: &'a [FieldDescriptor], key: &str) -> Option<&'a FieldDescriptor> {
+    descriptors
+        .iter()
+        .find(|descriptor| descriptor.key == key || descriptor.name == key)
+}
+
+/// 1-based line and column of the byte `offset`.
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or_default();
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn at(source_name: &str, content: &str, offset: usize) -> String {
+    let (line, column) = line_column(content, offset);
+    format!("{}:{}:{}", source_name, line, column)
+}
+
+fn describe(origin: &Origin, source_name: &str) -> String {
+    match origin {
+        Origin::File { line, column } => format!("{}:{}:{}", source_name, line, column),
+        Origin::Env(variable) => format!("environment variable {}", variable)
+    }
+}
+
+/// Errors already point at the config, so messages of invalid params don't repeat that they are invalid params.
+fn error_message(error: DetectorError) -> String {
+    match error {
+        DetectorError::InvalidParams(message) => {
+            let prefix = DetectorError::InvalidParams(String::new()).to_string();
+            message.strip_prefix(&prefix).unwrap_or(&message).to_string()
+        },
+        error => error.to_string()
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use std::fs;
+    use std::path::PathBuf;
+    use crate::{detector_params::AccountAgeMode, error::DetectorError, reg_date::RegDate};
+    use super::{ConfigFormat, ConfigLoader};
+
+    fn config_path(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("detector_{}_{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    pub fn test_config_files() {
+        struct TestCase {
+            file_name: &'static str,
+            content: &'static str,
+            env: Vec<(&'static str, &'static str)>,
+            expected: Result<(u32, u32), &'static str>
+        }
+
+        let test_cases = vec![
+            TestCase { file_name: "empty.toml", content: "", env: vec![], expected: Ok((200, 0)) },
+            TestCase {
+                file_name: "preset.toml",
+                content: "preset = \"strict\"\nban_score = 300\n",
+                env: vec![],
+                expected: Ok((300, 4))
+            },
+            TestCase {
+                file_name: "preset.json",
+                content: "{\n  \"preset\": \"strict\",\n  \"banScore\": 300\n}",
+                env: vec![],
+                expected: Ok((300, 4))
+            },
+            TestCase {
+                file_name: "env.toml",
+                content: "banScore = 300\nburst_message_count = 6\n",
+                env: vec![("DETECTOR_BAN_SCORE", "400"), ("DETECTOR_PRESET", "relaxed")],
+                expected: Ok((400, 6))
+            },
+            TestCase {
+                file_name: "out_of_range.toml",
+                content: "preset = \"strict\"\n\nban_score = 5000\n",
+                env: vec![],
+                expected: Err("out_of_range.toml:3:13: ban_score: ban_score is 5000")
+            },
+            // Score order is checked against the preset and the other fields
+            TestCase {
+                file_name: "scores.toml",
+                content: "hide_score = 250\nban_score = 300\n",
+                env: vec![],
+                expected: Ok((300, 0))
+            },
+            TestCase {
+                file_name: "strict_scores.toml",
+                content: "preset = \"strict\"\nban_score = 130\n",
+                env: vec![],
+                expected: Ok((130, 4))
+            },
+            TestCase {
+                file_name: "score_order.toml",
+                content: "hide_score = 250\nban_score = 240\n",
+                env: vec![],
+                expected: Err("score_order.toml: ban_score is 240, expected a value from 250 to 1000")
+            },
+            TestCase {
+                file_name: "unknown.json",
+                content: "{\n  \"banScore\": 300,\n  \"banScor\": 300\n}",
+                env: vec![],
+                expected: Err("unknown.json:3:14: unknown field banScor")
+            },
+            TestCase {
+                file_name: "syntax.toml",
+                content: "ban_score = 300\nburst_message_count = \n",
+                env: vec![],
+                expected: Err("syntax.toml:2:")
+            },
+            TestCase {
+                file_name: "syntax.json",
+                content: "{\n  \"banScore\": 300,\n}",
+                env: vec![],
+                expected: Err("syntax.json:3:1: trailing comma")
+            },
+            TestCase {
+                file_name: "bad_env.toml",
+                content: "",
+                env: vec![("DETECTOR_BAN_SCORE", "high")],
+                expected: Err("environment variable DETECTOR_BAN_SCORE: ban_score: invalid type")
+            },
+            TestCase {
+                file_name: "bad_preset.json",
+                content: "{\"preset\": \"lenient\"}",
+                env: vec![],
+                expected: Err("bad_preset.json:1:12: preset: unknown variant")
+            },
+        ];
+
+        for test_case in test_cases {
+            let path = config_path(test_case.file_name, test_case.content);
+            let env = test_case.env
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()));
+            let source_name = path.display().to_string();
+            let params = ConfigLoader::new().with_env(env).load(&path);
+            fs::remove_file(&path).unwrap();
+
+            match test_case.expected {
+                Ok(expected) => {
+                    let params = params.unwrap();
+                    assert_eq!(expected, (params.ban_score().0, params.burst_message_count().0), "file: {}", test_case.file_name);
+                },
+                Err(expected) => match params {
+                    Err(DetectorError::InvalidParams(message)) => {
+                        let message = message.replace(&source_name, test_case.file_name);
+                        assert!(message.starts_with(expected), "file: {}, error: {}", test_case.file_name, message);
+                    },
+                    _ => panic!("file: {} should fail", test_case.file_name)
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_config_values() {
+        let content = "account_age_mode = \"relative\"\nmin_reg_date = 2020-02-29\nminAccountAgeDays = 7\n";
+        let params = ConfigLoader::new()
+            .with_env(vec![("APP_MIN_ACCOUNT_AGE_DAYS".to_string(), "14".to_string())])
+            .with_env_prefix("APP_")
+            .parse(content, ConfigFormat::Toml, "inline")
+            .unwrap();
+        assert_eq!(AccountAgeMode::Relative, params.account_age_mode().0);
+        assert_eq!(14, params.min_account_age_days().0);
+        assert_eq!(RegDate::new(2020, 2, 29), params.min_reg_date_copy());
+
+        assert!(matches!(ConfigLoader::new().load(&PathBuf::from("detector.yaml")), Err(DetectorError::ConfigFailure(_))));
+        assert!(matches!(ConfigLoader::new().load(&PathBuf::from("missing_detector_config.toml")), Err(DetectorError::ConfigFailure(_))));
+
+        // Errors point at the top-level key, not at the same key nested in another value
+        let content = "{\n  \"notes\": {\"banScore\": 1, \"text\": \"\\\"banScore\\\": 2\"},\n  \"banScore\": 5000\n}";
+        let error = ConfigLoader::new().with_env(Vec::new()).parse(content, ConfigFormat::Json, "nested.json").err().unwrap();
+        assert!(error.to_string().contains("nested.json:3:15: banScore: ban_score is 5000"), "error: {}", error);
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::mem::size_of;
+use serde::{Deserialize, Serialize};
+
+use super::{detector_params::DetectorParams, message_data::MessageData};
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Cluster {
+    message_data: MessageData,
+    authors: HashSet<String>,
+    last_message_timestamp: u64,
+}
+
+/// Groups near-duplicate messages sent by different authors,
+/// so that coordinated spam can be caught even if every bot posts only once.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CopypastaIndex {
+    clusters: Vec<Cluster>,
+}
+
+impl CopypastaIndex {
+    pub fn new() -> Self {
+        CopypastaIndex {
+            clusters: Vec::with_capacity(100),
+        }
+    }
+
+    /// Adds the message to the index and, if it joined a cluster that is big enough,
+    /// returns the cluster size and every author that participated in it.
+    pub fn add_message(
+        &mut self,
+        author: &str,
+        timestamp: u64,
+        content: &str,
+        detector_params: &DetectorParams,
+    ) -> Option<(u32, Vec<String>)> {
+        if !detector_params.should_check_copypasta(content.chars().count() as u32) {
+            return None;
+        }
+
+        self.clusters.retain(|cluster| {
+            !detector_params.copypasta_expired(timestamp.saturating_sub(cluster.last_message_timestamp))
+        });
+
+        for cluster in self.clusters.iter_mut() {
+            if cluster.message_data.is_message_similar(content, detector_params) {
+                cluster.authors.insert(author.to_string());
+                cluster.last_message_timestamp = timestamp;
+
+                let cluster_size = cluster.authors.len() as u32;
+                if detector_params.is_copypasta(cluster_size) {
+                    return Some((cluster_size, cluster.authors.iter().cloned().collect()));
+                }
+
+                return None;
+            }
+        }
+
+        let mut authors = HashSet::with_capacity(4);
+        authors.insert(author.to_string());
+        self.clusters.push(Cluster {
+            message_data: MessageData::new(content.to_string()),
+            authors,
+            last_message_timestamp: timestamp,
+        });
+
+        // Every new message is compared with every cluster, so their number is bounded
+        if detector_params.too_many_copypasta_clusters(self.clusters.len()) {
+            let least_recent = self.clusters
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, cluster)| cluster.last_message_timestamp)
+                .map(|(position, _)| position);
+            if let Some(position) = least_recent {
+                self.clusters.remove(position);
+            }
+        }
+
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn estimated_size(&self) -> usize {
+        self.clusters
+            .iter()
+            .map(|cluster| {
+                size_of::<Cluster>() + cluster.message_data.message().len() +
+                    cluster.authors.iter().map(|author| author.len() + size_of::<String>()).sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+#[allow(unused_imports)]
+mod test {
+    use crate::detector_params::DetectorParams;
+    use super::CopypastaIndex;
+
+    #[test]
+    pub fn test_copypasta_clusters() {
+        struct TestCase {
+            messages: Vec<(&'static str, u64, &'static str)>,
+            expected_cluster_size: Option<u32>
+        }
+
+        // The rule is off by default
+        assert_eq!(None, CopypastaIndex::new().add_message("a", 0, "free gift cards at scam site", &DetectorParams::get_default()));
+        let params = DetectorParams::builder().copypasta_author_count(5).build().unwrap();
+
+        let test_cases = vec![
+            // Five different authors posting the same line
+            TestCase {
+                messages: vec![
+                    ("a", 0, "free gift cards at scam site"),
+                    ("b", 1_000, "free gift cards at scam site"),
+                    ("c", 2_000, "free gift card at scam site"),
+                    ("d", 3_000, "free gift cards at scam sit"),
+                    ("e", 4_000, "free gift cards at scam site"),
+                ],
+                expected_cluster_size: Some(5)
+            },
+            // An author repeating their own message is not a copypasta
+            TestCase {
+                messages: vec![
+                    ("a", 0, "free gift cards at scam site"),
+                    ("a", 1_000, "free gift cards at scam site"),
+                    ("a", 2_000, "free gift cards at scam site"),
+                    ("a", 3_000, "free gift cards at scam site"),
+                    ("a", 4_000, "free gift cards at scam site"),
+                ],
+                expected_cluster_size: None
+            },
+            // Messages that are too far apart don't form a cluster
+            TestCase {
+                messages: vec![
+                    ("a", 0, "free gift cards at scam site"),
+                    ("b", 100_000, "free gift cards at scam site"),
+                    ("c", 200_000, "free gift cards at scam site"),
+                    ("d", 300_000, "free gift cards at scam site"),
+                    ("e", 400_000, "free gift cards at scam site"),
+                ],
+                expected_cluster_size: None
+            },
+        ];
+
+        for test_case in test_cases {
+            let mut index = CopypastaIndex::new();
+            let mut result = None;
+            for (author, timestamp, content) in test_case.messages {
+                result = index.add_message(author, timestamp, content, &params);
+            }
+
+            assert_eq!(test_case.expected_cluster_size, result.map(|(size, _)| size));
+        }
+
+        // The least recently repeated message is forgotten first
+        let params = DetectorParams::builder().copypasta_author_count(2).max_copypasta_clusters(2).build().unwrap();
+        let mut index = CopypastaIndex::new();
+        index.add_message("a", 0, "free gift cards at scam site", &params);
+        index.add_message("b", 1_000, "join my discord server now", &params);
+        index.add_message("c", 2_000, "free gift cards at scam site", &params);
+        index.add_message("d", 3_000, "follow me for follow back", &params);
+        assert_eq!(2, index.len());
+        let size = index.estimated_size();
+        assert!(size > "free gift cards at scam site".len() + "follow me for follow back".len());
+        assert_eq!(Some(3), index.add_message("e", 4_000, "free gift cards at scam site", &params).map(|(size, _)| size));
+        assert_eq!(None, index.add_message("f", 5_000, "join my discord server now", &params));
+        assert!(index.estimated_size() > size);
+    }
+}
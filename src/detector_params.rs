@@ -1,21 +1,251 @@
 use std::borrow::Cow;
-use serde::{Deserialize, Serialize};
-use crate::{emoji::RegexPatterns, reg_date::RegDate};
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use crate::{author_data::Reason, chat_action::Badges, emoji::RegexPatterns, error::DetectorError, reg_date::RegDate, spam_score::Verdict};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SimilarityMetric {
+    Jaro,
+    JaroWinkler,
+    NormalizedLevenshtein,
+    SorensenDice
+}
+
+impl SimilarityMetric {
+    pub fn all() -> Vec<SimilarityMetric> {
+        vec![
+            SimilarityMetric::Jaro,
+            SimilarityMetric::JaroWinkler,
+            SimilarityMetric::NormalizedLevenshtein,
+            SimilarityMetric::SorensenDice
+        ]
+    }
+
+    pub fn similarity(&self, s1: &str, s2: &str) -> f64 {
+        match self {
+            SimilarityMetric::Jaro => strsim::jaro(s1, s2),
+            SimilarityMetric::JaroWinkler => strsim::jaro_winkler(s1, s2),
+            SimilarityMetric::NormalizedLevenshtein => strsim::normalized_levenshtein(s1, s2),
+            SimilarityMetric::SorensenDice => strsim::sorensen_dice(s1, s2)
+        }
+    }
+}
+
+/// How `avg_time_threshold` and `avg_length_threshold` are compared against the author's history.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AveragingMode {
+    /// Average over every message since the author's first one
+    Cumulative,
+    /// Average over the last `averaging_window` messages
+    SlidingWindow,
+    /// Exponential moving average with the smoothing factor `2 / (averaging_window + 1)`
+    Exponential
+}
+
+impl AveragingMode {
+    pub fn all() -> Vec<AveragingMode> {
+        vec![
+            AveragingMode::Cumulative,
+            AveragingMode::SlidingWindow,
+            AveragingMode::Exponential
+        ]
+    }
+}
+
+/// What happens to chat actions that arrive after newer actions were already processed.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LateMessagePolicy {
+    /// Actions are processed as soon as they arrive and are never dropped for being late.
+    /// An author's message that is older than their previous one is reported as `TimestampRegression`
+    Process,
+    /// Actions are processed as soon as they arrive, and the ones older than
+    /// already processed actions of any author are dropped
+    Drop,
+    /// Actions are held back for `max_message_lateness` to give late actions a chance
+    /// to be processed in order. Actions that are late even for that are dropped
+    Reorder
+}
+
+impl LateMessagePolicy {
+    pub fn all() -> Vec<LateMessagePolicy> {
+        vec![
+            LateMessagePolicy::Process,
+            LateMessagePolicy::Drop,
+            LateMessagePolicy::Reorder
+        ]
+    }
+}
+
+/// How `acc_too_young` decides that an account is too young.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountAgeMode {
+    /// Accounts registered on or after `min_reg_date`
+    Absolute,
+    /// Accounts registered less than `min_account_age_days` days before the message
+    Relative
+}
+
+impl AccountAgeMode {
+    pub fn all() -> Vec<AccountAgeMode> {
+        vec![
+            AccountAgeMode::Absolute,
+            AccountAgeMode::Relative
+        ]
+    }
+}
+
+/// How messages from authors with a badge are moderated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BadgePolicy {
+    /// Messages are not checked at all
+    Exempt,
+    /// Scores have to be `relaxed_score_percent` percent of the usual ones to count
+    Relaxed,
+    /// Same as authors without badges
+    #[default]
+    Normal
+}
+
+impl BadgePolicy {
+    pub fn all() -> Vec<BadgePolicy> {
+        vec![
+            BadgePolicy::Exempt,
+            BadgePolicy::Relaxed,
+            BadgePolicy::Normal
+        ]
+    }
+
+    /// The more lenient of the two policies.
+    pub fn most_lenient(self, other: BadgePolicy) -> BadgePolicy {
+        match (self, other) {
+            (BadgePolicy::Exempt, _) | (_, BadgePolicy::Exempt) => BadgePolicy::Exempt,
+            (BadgePolicy::Relaxed, _) | (_, BadgePolicy::Relaxed) => BadgePolicy::Relaxed,
+            _ => BadgePolicy::Normal
+        }
+    }
+}
+
+/// What happens to the report of an author who supports the stream.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SupportReportPolicy {
+    /// The report is removed
+    Clear,
+    /// The author stays reported and every later message of theirs is reported too, support only prevents new reports
+    Keep
+}
+
+impl SupportReportPolicy {
+    pub fn all() -> Vec<SupportReportPolicy> {
+        vec![
+            SupportReportPolicy::Clear,
+            SupportReportPolicy::Keep
+        ]
+    }
+}
+
+pub struct SelectFieldDescriptor<T> {
+    pub name: String,
+    pub options: Vec<T>,
+}
+
+impl SelectFieldDescriptor<AccountAgeMode> {
+    pub fn account_age_mode() -> SelectFieldDescriptor<AccountAgeMode> {
+        SelectFieldDescriptor {
+            name: "account_age_mode".to_string(),
+            options: AccountAgeMode::all()
+        }
+    }
+}
+
+impl SelectFieldDescriptor<BadgePolicy> {
+    pub fn member_policy() -> SelectFieldDescriptor<BadgePolicy> {
+        SelectFieldDescriptor::badge_policy("member_policy")
+    }
+
+    pub fn verified_policy() -> SelectFieldDescriptor<BadgePolicy> {
+        SelectFieldDescriptor::badge_policy("verified_policy")
+    }
+
+    pub fn owner_policy() -> SelectFieldDescriptor<BadgePolicy> {
+        SelectFieldDescriptor::badge_policy("owner_policy")
+    }
+
+    pub fn moderator_policy() -> SelectFieldDescriptor<BadgePolicy> {
+        SelectFieldDescriptor::badge_policy("moderator_policy")
+    }
+
+    fn badge_policy(name: &str) -> SelectFieldDescriptor<BadgePolicy> {
+        SelectFieldDescriptor {
+            name: name.to_string(),
+            options: BadgePolicy::all()
+        }
+    }
+}
+
+impl SelectFieldDescriptor<SupportReportPolicy> {
+    pub fn support_report_policy() -> SelectFieldDescriptor<SupportReportPolicy> {
+        SelectFieldDescriptor {
+            name: "support_report_policy".to_string(),
+            options: SupportReportPolicy::all()
+        }
+    }
+}
+
+impl SelectFieldDescriptor<AveragingMode> {
+    pub fn averaging_mode() -> SelectFieldDescriptor<AveragingMode> {
+        SelectFieldDescriptor {
+            name: "averaging_mode".to_string(),
+            options: AveragingMode::all()
+        }
+    }
+}
+
+impl SelectFieldDescriptor<LateMessagePolicy> {
+    pub fn late_message_policy() -> SelectFieldDescriptor<LateMessagePolicy> {
+        SelectFieldDescriptor {
+            name: "late_message_policy".to_string(),
+            options: LateMessagePolicy::all()
+        }
+    }
+}
+
+impl SelectFieldDescriptor<SimilarityMetric> {
+    pub fn similarity_metric() -> SelectFieldDescriptor<SimilarityMetric> {
+        SelectFieldDescriptor {
+            name: "similarity_metric".to_string(),
+            options: SimilarityMetric::all()
+        }
+    }
+}
 
 pub struct TextFieldDescriptor {
     pub name: String,
     pub min: u32,
     pub max: u32,
+    /// Increment for UI controls, `validate` doesn't require values to be multiples of it
     pub step: u32,
+    /// 0 turns the rule or limit off, and is accepted even if `min` is greater
+    pub zero_disables: bool,
 }
 
 impl TextFieldDescriptor {
+    pub fn accepts(&self, value: u32) -> bool {
+        (value == 0 && self.zero_disables) || (self.min..=self.max).contains(&value)
+    }
+
     pub fn avg_time_threshold() -> TextFieldDescriptor {
         TextFieldDescriptor {
             name: "avg_time_threshold".to_string(),
             min: 1,
             max: 120000,
-            step: 1
+            step: 1,
+            zero_disables: true
         }
     }
 
@@ -24,7 +254,38 @@ impl TextFieldDescriptor {
             name: "avg_time_min_message_count".to_string(),
             min: 2,
             max: 99,
-            step: 1
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn averaging_window() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "averaging_window".to_string(),
+            min: 2,
+            max: 99,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn burst_message_count() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "burst_message_count".to_string(),
+            min: 0,
+            max: 99,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn burst_time_window() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "burst_time_window".to_string(),
+            min: 1,
+            max: 120000,
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -33,7 +294,8 @@ impl TextFieldDescriptor {
             name: "similarity_message_count".to_string(),
             min: 2,
             max: 99,
-            step: 1
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -42,7 +304,18 @@ impl TextFieldDescriptor {
             name: "similarity_min_message_length".to_string(),
             min: 1,
             max: 300,
-            step: 1
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn similarity_threshold() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "similarity_threshold".to_string(),
+            min: 1,
+            max: 99,
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -51,7 +324,8 @@ impl TextFieldDescriptor {
             name: "avg_length_threshold".to_string(),
             min: 1,
             max: 300,
-            step: 1
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -60,7 +334,258 @@ impl TextFieldDescriptor {
             name: "avg_length_message_count".to_string(),
             min: 0,
             max: 99,
-            step: 1
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn retracted_message_count() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "retracted_message_count".to_string(),
+            min: 0,
+            max: 99,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn retraction_time_threshold() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "retraction_time_threshold".to_string(),
+            min: 0,
+            max: 120000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn copypasta_author_count() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "copypasta_author_count".to_string(),
+            min: 0,
+            max: 99,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn copypasta_time_window() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "copypasta_time_window".to_string(),
+            min: 1,
+            max: 600000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn max_copypasta_clusters() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "max_copypasta_clusters".to_string(),
+            min: 0,
+            max: 10000,
+            step: 10,
+            zero_disables: true
+        }
+    }
+
+    pub fn author_idle_timeout() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "author_idle_timeout".to_string(),
+            min: 0,
+            max: 86400000,
+            step: 1000,
+            zero_disables: true
+        }
+    }
+
+    pub fn max_tracked_authors() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "max_tracked_authors".to_string(),
+            min: 0,
+            max: 1000000,
+            step: 100,
+            zero_disables: true
+        }
+    }
+
+    pub fn max_messages_per_author() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "max_messages_per_author".to_string(),
+            min: 0,
+            max: 10000,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn max_message_lateness() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "max_message_lateness".to_string(),
+            min: 0,
+            max: 60000,
+            step: 100,
+            zero_disables: false
+        }
+    }
+
+    pub fn remembered_message_ids() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "remembered_message_ids".to_string(),
+            min: 0,
+            max: 1000000,
+            step: 100,
+            zero_disables: true
+        }
+    }
+
+    pub fn slow_mode_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "slow_mode_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn burst_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "burst_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn too_fast_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "too_fast_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn too_long_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "too_long_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn similar_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "similar_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn retracted_message_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "retracted_message_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn copypasta_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "copypasta_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn young_account_weight() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "young_account_weight".to_string(),
+            min: 0,
+            max: 100,
+            step: 1,
+            zero_disables: true
+        }
+    }
+
+    pub fn flag_score() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "flag_score".to_string(),
+            min: 1,
+            max: 1000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn hide_score() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "hide_score".to_string(),
+            min: 1,
+            max: 1000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn ban_score() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "ban_score".to_string(),
+            min: 1,
+            max: 1000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn relaxed_score_percent() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "relaxed_score_percent".to_string(),
+            min: 100,
+            max: 1000,
+            step: 10,
+            zero_disables: false
+        }
+    }
+
+    pub fn min_support_amount() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "min_support_amount".to_string(),
+            min: 0,
+            max: 1000000,
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    pub fn support_exemption_duration() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "support_exemption_duration".to_string(),
+            min: 0,
+            max: 86400000,
+            step: 1000,
+            zero_disables: false
+        }
+    }
+
+    pub fn min_account_age_days() -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            name: "min_account_age_days".to_string(),
+            min: 1,
+            max: 3650,
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -69,7 +594,8 @@ impl TextFieldDescriptor {
             name: "min_reg_date_year".to_string(),
             min: 2000,
             max: 2100,
-            step: 1
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -78,7 +604,8 @@ impl TextFieldDescriptor {
             name: "min_reg_date_month".to_string(),
             min: 1,
             max: 12,
-            step: 1
+            step: 1,
+            zero_disables: false
         }
     }
 
@@ -87,25 +614,396 @@ impl TextFieldDescriptor {
             name: "min_reg_date_day".to_string(),
             min: 1,
             max: 31,
-            step: 1
+            step: 1,
+            zero_disables: false
+        }
+    }
+
+    /// Same as `min_reg_date_day`, limited to the days of the given month.
+    pub fn min_reg_date_day_of(year: u16, month: u8) -> TextFieldDescriptor {
+        TextFieldDescriptor {
+            max: RegDate::days_in_month(year, month) as u32,
+            ..TextFieldDescriptor::min_reg_date_day()
         }
     }
 }
 
+/// Deserialization rejects values that are out of their descriptor's range, see `validate`.
 #[derive(Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(remote = "Self", rename_all = "camelCase")]
 pub struct DetectorParams {
     avg_time_threshold: u32,
     avg_time_min_message_count: u32,
+    #[serde(default = "DetectorParams::default_averaging_mode")]
+    averaging_mode: AveragingMode,
+    #[serde(default = "DetectorParams::default_averaging_window")]
+    averaging_window: u32,
+    #[serde(default = "DetectorParams::default_burst_message_count")]
+    burst_message_count: u32,
+    #[serde(default = "DetectorParams::default_burst_time_window")]
+    burst_time_window: u32,
     similarity_message_count: u32,
     similarity_min_message_length: u32,
+    #[serde(default = "DetectorParams::default_similarity_metric")]
+    similarity_metric: SimilarityMetric,
+    #[serde(default = "DetectorParams::default_similarity_threshold")]
+    similarity_threshold: u32,
     avg_length_threshold: u32,
     avg_length_message_count: u32,
+    #[serde(default = "DetectorParams::default_retracted_message_count")]
+    retracted_message_count: u32,
+    #[serde(default = "DetectorParams::default_retraction_time_threshold")]
+    retraction_time_threshold: u32,
+    #[serde(default = "DetectorParams::default_copypasta_author_count")]
+    copypasta_author_count: u32,
+    #[serde(default = "DetectorParams::default_copypasta_time_window")]
+    copypasta_time_window: u32,
+    #[serde(default = "DetectorParams::default_max_copypasta_clusters")]
+    max_copypasta_clusters: u32,
+    #[serde(default = "DetectorParams::default_author_idle_timeout")]
+    author_idle_timeout: u32,
+    #[serde(default = "DetectorParams::default_max_tracked_authors")]
+    max_tracked_authors: u32,
+    #[serde(default = "DetectorParams::default_max_messages_per_author")]
+    max_messages_per_author: u32,
+    #[serde(default = "DetectorParams::default_late_message_policy")]
+    late_message_policy: LateMessagePolicy,
+    #[serde(default = "DetectorParams::default_max_message_lateness")]
+    max_message_lateness: u32,
+    #[serde(default = "DetectorParams::default_remembered_message_ids")]
+    remembered_message_ids: u32,
+    #[serde(default = "DetectorParams::default_slow_mode_weight")]
+    slow_mode_weight: u32,
+    #[serde(default = "DetectorParams::default_burst_weight")]
+    burst_weight: u32,
+    #[serde(default = "DetectorParams::default_too_fast_weight")]
+    too_fast_weight: u32,
+    #[serde(default = "DetectorParams::default_too_long_weight")]
+    too_long_weight: u32,
+    #[serde(default = "DetectorParams::default_similar_weight")]
+    similar_weight: u32,
+    #[serde(default = "DetectorParams::default_retracted_message_weight")]
+    retracted_message_weight: u32,
+    #[serde(default = "DetectorParams::default_copypasta_weight")]
+    copypasta_weight: u32,
+    #[serde(default = "DetectorParams::default_young_account_weight")]
+    young_account_weight: u32,
+    #[serde(default = "DetectorParams::default_flag_score")]
+    flag_score: u32,
+    #[serde(default = "DetectorParams::default_hide_score")]
+    hide_score: u32,
+    #[serde(default = "DetectorParams::default_ban_score")]
+    ban_score: u32,
+    #[serde(default = "DetectorParams::default_member_policy")]
+    member_policy: BadgePolicy,
+    #[serde(default = "DetectorParams::default_verified_policy")]
+    verified_policy: BadgePolicy,
+    #[serde(default = "DetectorParams::default_owner_policy")]
+    owner_policy: BadgePolicy,
+    #[serde(default = "DetectorParams::default_moderator_policy")]
+    moderator_policy: BadgePolicy,
+    #[serde(default = "DetectorParams::default_relaxed_score_percent")]
+    relaxed_score_percent: u32,
+    #[serde(default = "DetectorParams::default_min_support_amount")]
+    min_support_amount: u32,
+    #[serde(default = "DetectorParams::default_support_currency")]
+    support_currency: String,
+    #[serde(default = "DetectorParams::default_support_exemption_duration")]
+    support_exemption_duration: u32,
+    #[serde(default = "DetectorParams::default_support_report_policy")]
+    support_report_policy: SupportReportPolicy,
+    #[serde(default = "DetectorParams::default_account_age_mode")]
+    account_age_mode: AccountAgeMode,
+    #[serde(default = "DetectorParams::default_min_account_age_days")]
+    min_account_age_days: u32,
     min_reg_date: RegDate,
     #[serde(skip, default = "DetectorParams::regex")]
     regex_patterns: RegexPatterns,
 }
 
+impl Serialize for DetectorParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DetectorParams::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DetectorParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let params = DetectorParams::deserialize(deserializer)?;
+        params
+            .validate()
+            .map_err(|violations| de::Error::custom(DetectorError::InvalidParams(join_violations(&violations))))?;
+        Ok(params)
+    }
+}
+
+/// A field of `DetectorParams` that is outside of the range declared by its `TextFieldDescriptor`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamViolation {
+    pub field: String,
+    pub value: u32,
+    pub min: u32,
+    pub max: u32
+}
+
+impl fmt::Display for ParamViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is {}, expected a value from {} to {}", self.field, self.value, self.min, self.max)
+    }
+}
+
+/// Builds `DetectorParams` starting from the defaults, checking every field when it's done.
+pub struct DetectorParamsBuilder {
+    params: DetectorParams
+}
+
+impl DetectorParamsBuilder {
+    pub fn avg_time_threshold(mut self, avg_time_threshold: u32) -> Self {
+        self.params.avg_time_threshold = avg_time_threshold;
+        self
+    }
+
+    pub fn avg_time_min_message_count(mut self, avg_time_min_message_count: u32) -> Self {
+        self.params.avg_time_min_message_count = avg_time_min_message_count;
+        self
+    }
+
+    pub fn averaging_mode(mut self, averaging_mode: AveragingMode) -> Self {
+        self.params.averaging_mode = averaging_mode;
+        self
+    }
+
+    pub fn averaging_window(mut self, averaging_window: u32) -> Self {
+        self.params.averaging_window = averaging_window;
+        self
+    }
+
+    pub fn burst_message_count(mut self, burst_message_count: u32) -> Self {
+        self.params.burst_message_count = burst_message_count;
+        self
+    }
+
+    pub fn burst_time_window(mut self, burst_time_window: u32) -> Self {
+        self.params.burst_time_window = burst_time_window;
+        self
+    }
+
+    pub fn similarity_message_count(mut self, similarity_message_count: u32) -> Self {
+        self.params.similarity_message_count = similarity_message_count;
+        self
+    }
+
+    pub fn similarity_min_message_length(mut self, similarity_min_message_length: u32) -> Self {
+        self.params.similarity_min_message_length = similarity_min_message_length;
+        self
+    }
+
+    pub fn similarity_metric(mut self, similarity_metric: SimilarityMetric) -> Self {
+        self.params.similarity_metric = similarity_metric;
+        self
+    }
+
+    pub fn similarity_threshold(mut self, similarity_threshold: u32) -> Self {
+        self.params.similarity_threshold = similarity_threshold;
+        self
+    }
+
+    pub fn avg_length_threshold(mut self, avg_length_threshold: u32) -> Self {
+        self.params.avg_length_threshold = avg_length_threshold;
+        self
+    }
+
+    pub fn avg_length_message_count(mut self, avg_length_message_count: u32) -> Self {
+        self.params.avg_length_message_count = avg_length_message_count;
+        self
+    }
+
+    pub fn retracted_message_count(mut self, retracted_message_count: u32) -> Self {
+        self.params.retracted_message_count = retracted_message_count;
+        self
+    }
+
+    pub fn retraction_time_threshold(mut self, retraction_time_threshold: u32) -> Self {
+        self.params.retraction_time_threshold = retraction_time_threshold;
+        self
+    }
+
+    pub fn copypasta_author_count(mut self, copypasta_author_count: u32) -> Self {
+        self.params.copypasta_author_count = copypasta_author_count;
+        self
+    }
+
+    pub fn copypasta_time_window(mut self, copypasta_time_window: u32) -> Self {
+        self.params.copypasta_time_window = copypasta_time_window;
+        self
+    }
+
+    pub fn max_copypasta_clusters(mut self, max_copypasta_clusters: u32) -> Self {
+        self.params.max_copypasta_clusters = max_copypasta_clusters;
+        self
+    }
+
+    pub fn author_idle_timeout(mut self, author_idle_timeout: u32) -> Self {
+        self.params.author_idle_timeout = author_idle_timeout;
+        self
+    }
+
+    pub fn max_tracked_authors(mut self, max_tracked_authors: u32) -> Self {
+        self.params.max_tracked_authors = max_tracked_authors;
+        self
+    }
+
+    pub fn max_messages_per_author(mut self, max_messages_per_author: u32) -> Self {
+        self.params.max_messages_per_author = max_messages_per_author;
+        self
+    }
+
+    pub fn late_message_policy(mut self, late_message_policy: LateMessagePolicy) -> Self {
+        self.params.late_message_policy = late_message_policy;
+        self
+    }
+
+    pub fn max_message_lateness(mut self, max_message_lateness: u32) -> Self {
+        self.params.max_message_lateness = max_message_lateness;
+        self
+    }
+
+    pub fn remembered_message_ids(mut self, remembered_message_ids: u32) -> Self {
+        self.params.remembered_message_ids = remembered_message_ids;
+        self
+    }
+
+    pub fn slow_mode_weight(mut self, slow_mode_weight: u32) -> Self {
+        self.params.slow_mode_weight = slow_mode_weight;
+        self
+    }
+
+    pub fn burst_weight(mut self, burst_weight: u32) -> Self {
+        self.params.burst_weight = burst_weight;
+        self
+    }
+
+    pub fn too_fast_weight(mut self, too_fast_weight: u32) -> Self {
+        self.params.too_fast_weight = too_fast_weight;
+        self
+    }
+
+    pub fn too_long_weight(mut self, too_long_weight: u32) -> Self {
+        self.params.too_long_weight = too_long_weight;
+        self
+    }
+
+    pub fn similar_weight(mut self, similar_weight: u32) -> Self {
+        self.params.similar_weight = similar_weight;
+        self
+    }
+
+    pub fn retracted_message_weight(mut self, retracted_message_weight: u32) -> Self {
+        self.params.retracted_message_weight = retracted_message_weight;
+        self
+    }
+
+    pub fn copypasta_weight(mut self, copypasta_weight: u32) -> Self {
+        self.params.copypasta_weight = copypasta_weight;
+        self
+    }
+
+    pub fn young_account_weight(mut self, young_account_weight: u32) -> Self {
+        self.params.young_account_weight = young_account_weight;
+        self
+    }
+
+    pub fn flag_score(mut self, flag_score: u32) -> Self {
+        self.params.flag_score = flag_score;
+        self
+    }
+
+    pub fn hide_score(mut self, hide_score: u32) -> Self {
+        self.params.hide_score = hide_score;
+        self
+    }
+
+    pub fn ban_score(mut self, ban_score: u32) -> Self {
+        self.params.ban_score = ban_score;
+        self
+    }
+
+    pub fn member_policy(mut self, member_policy: BadgePolicy) -> Self {
+        self.params.member_policy = member_policy;
+        self
+    }
+
+    pub fn verified_policy(mut self, verified_policy: BadgePolicy) -> Self {
+        self.params.verified_policy = verified_policy;
+        self
+    }
+
+    pub fn owner_policy(mut self, owner_policy: BadgePolicy) -> Self {
+        self.params.owner_policy = owner_policy;
+        self
+    }
+
+    pub fn moderator_policy(mut self, moderator_policy: BadgePolicy) -> Self {
+        self.params.moderator_policy = moderator_policy;
+        self
+    }
+
+    pub fn relaxed_score_percent(mut self, relaxed_score_percent: u32) -> Self {
+        self.params.relaxed_score_percent = relaxed_score_percent;
+        self
+    }
+
+    pub fn min_support_amount(mut self, min_support_amount: u32) -> Self {
+        self.params.min_support_amount = min_support_amount;
+        self
+    }
+
+    pub fn support_exemption_duration(mut self, support_exemption_duration: u32) -> Self {
+        self.params.support_exemption_duration = support_exemption_duration;
+        self
+    }
+
+    pub fn support_currency(mut self, support_currency: &str) -> Self {
+        self.params.support_currency = support_currency.to_string();
+        self
+    }
+
+    pub fn support_report_policy(mut self, support_report_policy: SupportReportPolicy) -> Self {
+        self.params.support_report_policy = support_report_policy;
+        self
+    }
+
+    pub fn account_age_mode(mut self, account_age_mode: AccountAgeMode) -> Self {
+        self.params.account_age_mode = account_age_mode;
+        self
+    }
+
+    pub fn min_account_age_days(mut self, min_account_age_days: u32) -> Self {
+        self.params.min_account_age_days = min_account_age_days;
+        self
+    }
+
+    pub fn min_reg_date(mut self, min_reg_date: RegDate) -> Self {
+        self.params.min_reg_date = min_reg_date;
+        self
+    }
+
+    pub fn build(self) -> Result<DetectorParams, DetectorError> {
+        self.params
+            .validate()
+            .map(|_| self.params)
+            .map_err(|violations| DetectorError::InvalidParams(join_violations(&violations)))
+    }
+}
+
+fn join_violations(violations: &[ParamViolation]) -> String {
+    violations
+        .iter()
+        .map(|violation| violation.to_string())
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
 impl DetectorParams {
     pub fn get_default() -> Self {
         let patterns = RegexPatterns::new();
@@ -113,15 +1011,57 @@ impl DetectorParams {
         DetectorParams {
             avg_time_threshold: 2000,
             avg_time_min_message_count: 3,
+            averaging_mode: DetectorParams::default_averaging_mode(),
+            averaging_window: DetectorParams::default_averaging_window(),
+            burst_message_count: DetectorParams::default_burst_message_count(),
+            burst_time_window: DetectorParams::default_burst_time_window(),
             similarity_message_count: 3,
             similarity_min_message_length: 10,
+            similarity_metric: DetectorParams::default_similarity_metric(),
+            similarity_threshold: DetectorParams::default_similarity_threshold(),
             avg_length_threshold: 15,
             avg_length_message_count: 5,
+            retracted_message_count: DetectorParams::default_retracted_message_count(),
+            retraction_time_threshold: DetectorParams::default_retraction_time_threshold(),
+            copypasta_author_count: DetectorParams::default_copypasta_author_count(),
+            copypasta_time_window: DetectorParams::default_copypasta_time_window(),
+            max_copypasta_clusters: DetectorParams::default_max_copypasta_clusters(),
+            author_idle_timeout: DetectorParams::default_author_idle_timeout(),
+            max_tracked_authors: DetectorParams::default_max_tracked_authors(),
+            max_messages_per_author: DetectorParams::default_max_messages_per_author(),
+            late_message_policy: DetectorParams::default_late_message_policy(),
+            max_message_lateness: DetectorParams::default_max_message_lateness(),
+            remembered_message_ids: DetectorParams::default_remembered_message_ids(),
+            slow_mode_weight: DetectorParams::default_slow_mode_weight(),
+            burst_weight: DetectorParams::default_burst_weight(),
+            too_fast_weight: DetectorParams::default_too_fast_weight(),
+            too_long_weight: DetectorParams::default_too_long_weight(),
+            similar_weight: DetectorParams::default_similar_weight(),
+            retracted_message_weight: DetectorParams::default_retracted_message_weight(),
+            copypasta_weight: DetectorParams::default_copypasta_weight(),
+            young_account_weight: DetectorParams::default_young_account_weight(),
+            flag_score: DetectorParams::default_flag_score(),
+            hide_score: DetectorParams::default_hide_score(),
+            ban_score: DetectorParams::default_ban_score(),
+            member_policy: DetectorParams::default_member_policy(),
+            verified_policy: DetectorParams::default_verified_policy(),
+            owner_policy: DetectorParams::default_owner_policy(),
+            moderator_policy: DetectorParams::default_moderator_policy(),
+            relaxed_score_percent: DetectorParams::default_relaxed_score_percent(),
+            min_support_amount: DetectorParams::default_min_support_amount(),
+            support_currency: DetectorParams::default_support_currency(),
+            support_exemption_duration: DetectorParams::default_support_exemption_duration(),
+            support_report_policy: DetectorParams::default_support_report_policy(),
+            account_age_mode: DetectorParams::default_account_age_mode(),
+            min_account_age_days: DetectorParams::default_min_account_age_days(),
             min_reg_date: RegDate::default(),
             regex_patterns: patterns,
         }
     }
 
+    /// Params with the original fields of the detector, every field added since then has its default.
+    /// Its arguments are frozen, new fields are only set through `builder` and serde defaults.
+    /// Doesn't check the values, use `builder` for params that come from the outside.
     pub fn new(
         avg_time_threshold: u32,
         avg_time_min_message_count: u32,
@@ -131,8 +1071,6 @@ impl DetectorParams {
         avg_length_message_count: u32,
         min_reg_date: RegDate
     ) -> Self {
-        let patterns = RegexPatterns::new();
-
         DetectorParams {
             avg_time_threshold,
             avg_time_min_message_count,
@@ -141,10 +1079,101 @@ impl DetectorParams {
             avg_length_threshold,
             avg_length_message_count,
             min_reg_date,
-            regex_patterns: patterns,
+            ..DetectorParams::get_default()
+        }
+    }
+
+    pub fn builder() -> DetectorParamsBuilder {
+        DetectorParamsBuilder {
+            params: DetectorParams::get_default()
+        }
+    }
+
+    /// Checks every numeric field against its `TextFieldDescriptor` and returns every violation at once.
+    /// Scores must also be ordered: `flag_score` ≤ `hide_score` ≤ `ban_score`.
+    pub fn validate(&self) -> Result<(), Vec<ParamViolation>> {
+        let mut violations: Vec<ParamViolation> = self.text_fields()
+            .into_iter()
+            .filter(|(value, descriptor)| !descriptor.accepts(*value))
+            .map(|(value, descriptor)| ParamViolation {
+                field: descriptor.name,
+                value,
+                min: descriptor.min,
+                max: descriptor.max
+            })
+            .collect();
+
+        if self.hide_score < self.flag_score {
+            violations.push(ParamViolation {
+                field: "hide_score".to_string(),
+                value: self.hide_score,
+                min: self.flag_score,
+                max: TextFieldDescriptor::hide_score().max
+            });
+        }
+        if self.ban_score < self.hide_score {
+            violations.push(ParamViolation {
+                field: "ban_score".to_string(),
+                value: self.ban_score,
+                min: self.hide_score,
+                max: TextFieldDescriptor::ban_score().max
+            });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
         }
     }
 
+    fn text_fields(&self) -> Vec<(u32, TextFieldDescriptor)> {
+        let (year, year_descriptor) = self.min_reg_date_year();
+        let (month, month_descriptor) = self.min_reg_date_month();
+        let (day, day_descriptor) = self.min_reg_date_day();
+
+        vec![
+            self.avg_time_threshold(),
+            self.avg_time_min_message_count(),
+            self.averaging_window(),
+            self.burst_message_count(),
+            self.burst_time_window(),
+            self.similarity_message_count(),
+            self.similarity_min_message_length(),
+            self.similarity_threshold(),
+            self.avg_length_threshold(),
+            self.avg_length_message_count(),
+            self.retracted_message_count(),
+            self.retraction_time_threshold(),
+            self.copypasta_author_count(),
+            self.copypasta_time_window(),
+            self.max_copypasta_clusters(),
+            self.author_idle_timeout(),
+            self.max_tracked_authors(),
+            self.max_messages_per_author(),
+            self.max_message_lateness(),
+            self.remembered_message_ids(),
+            self.slow_mode_weight(),
+            self.burst_weight(),
+            self.too_fast_weight(),
+            self.too_long_weight(),
+            self.similar_weight(),
+            self.retracted_message_weight(),
+            self.copypasta_weight(),
+            self.young_account_weight(),
+            self.flag_score(),
+            self.hide_score(),
+            self.ban_score(),
+            self.relaxed_score_percent(),
+            self.min_support_amount(),
+            self.support_exemption_duration(),
+            self.min_account_age_days(),
+            (year as u32, year_descriptor),
+            (month as u32, month_descriptor),
+            (day as u32, day_descriptor),
+        ]
+    }
+
     pub fn is_too_fast(&self, current_delay: u32, sent_messages_count: u32) -> bool {
         self.avg_time_threshold != 0 &&
         current_delay != 0 &&
@@ -152,22 +1181,179 @@ impl DetectorParams {
         sent_messages_count >= self.avg_time_min_message_count
     }
 
+    pub fn is_burst(&self, messages_count: usize, time_span: u64) -> bool {
+        self.burst_message_count != 0 &&
+        messages_count >= self.burst_message_count as usize &&
+        time_span <= self.burst_time_window as u64
+    }
+
+    pub fn current_average(&self, cumulative: f32, sliding_window: f32, exponential: f32) -> f32 {
+        match self.averaging_mode {
+            AveragingMode::Cumulative => cumulative,
+            AveragingMode::SlidingWindow => sliding_window,
+            AveragingMode::Exponential => exponential
+        }
+    }
+
+    pub fn burst_message_count_usize(&self) -> usize {
+        self.burst_message_count as usize
+    }
+
+    pub fn averaging_window_size(&self) -> usize {
+        self.averaging_window as usize
+    }
+
     pub fn sent_too_many_similar_messages(&self, similar_messages_count: u32) -> bool {
         similar_messages_count >= self.similarity_message_count
     }
 
+    pub fn are_messages_similar(&self, s1: &str, s2: &str) -> bool {
+        self.similarity_metric.similarity(s1, s2) > self.similarity_threshold as f64 / 100.0
+    }
+
     pub fn messages_are_too_long(&self, current_average_message_length: f32, sent_messages_count: u32) -> bool {
         self.avg_length_message_count != 0 &&
         sent_messages_count >= self.avg_length_message_count &&
         current_average_message_length >= self.avg_length_threshold as f32
     }
 
+    pub fn retracted_too_quickly(&self, time_since_message: u32) -> bool {
+        self.retraction_time_threshold == 0 ||
+        time_since_message < self.retraction_time_threshold
+    }
+
+    pub fn retracted_too_many_messages(&self, retracted_messages_count: u32) -> bool {
+        self.retracted_message_count != 0 &&
+        retracted_messages_count >= self.retracted_message_count
+    }
+
+    pub fn should_check_copypasta(&self, message_len: u32) -> bool {
+        self.copypasta_author_count != 0 && self.should_check_message(message_len)
+    }
+
+    pub fn copypasta_expired(&self, time_since_last_message: u64) -> bool {
+        time_since_last_message > self.copypasta_time_window as u64
+    }
+
+    pub fn too_many_copypasta_clusters(&self, clusters_count: usize) -> bool {
+        self.max_copypasta_clusters != 0 &&
+        clusters_count > self.max_copypasta_clusters as usize
+    }
+
+    pub fn is_copypasta(&self, authors_count: u32) -> bool {
+        self.copypasta_author_count != 0 &&
+        authors_count >= self.copypasta_author_count
+    }
+
+    pub fn author_is_idle(&self, time_since_last_message: u64) -> bool {
+        self.author_idle_timeout != 0 &&
+        time_since_last_message > self.author_idle_timeout as u64
+    }
+
+    pub fn too_many_tracked_authors(&self, authors_count: usize) -> bool {
+        self.max_tracked_authors != 0 &&
+        authors_count > self.max_tracked_authors as usize
+    }
+
+    pub fn max_tracked_authors_count(&self) -> usize {
+        self.max_tracked_authors as usize
+    }
+
+    pub fn too_many_stored_messages(&self, stored_messages_count: u32) -> bool {
+        self.max_messages_per_author != 0 &&
+        stored_messages_count > self.max_messages_per_author
+    }
+
     pub fn should_check_message(&self, message_len: u32) -> bool {
         message_len != 0 && message_len >= self.similarity_min_message_length
     }
 
-    pub fn acc_too_young(&self, reg_date: &RegDate) -> bool {
-        reg_date >= &self.min_reg_date
+    /// `timestamp` is the time of the message in milliseconds.
+    /// Accounts without a known registration date are considered too young.
+    pub fn acc_too_young(&self, reg_date: Option<&RegDate>, timestamp: u64) -> bool {
+        let reg_date = match reg_date {
+            Some(reg_date) => reg_date,
+            None => return true
+        };
+
+        match self.account_age_mode {
+            AccountAgeMode::Absolute => reg_date >= &self.min_reg_date,
+            AccountAgeMode::Relative => {
+                // Compared as days since the epoch, so timestamps past the last RegDate still work
+                let message_day = (timestamp / 1000 / 86400) as i64;
+                message_day - reg_date.days_since_epoch() < self.min_account_age_days as i64
+            }
+        }
+    }
+
+    pub fn weight(&self, reason: &Reason) -> u32 {
+        match reason {
+            Reason::SlowMode => self.slow_mode_weight,
+            Reason::Burst(_) => self.burst_weight,
+            Reason::TooFast(_) => self.too_fast_weight,
+            Reason::TooLong(_) => self.too_long_weight,
+            Reason::Similar => self.similar_weight,
+            Reason::RetractedMessage => self.retracted_message_weight,
+            Reason::Copypasta(_) => self.copypasta_weight,
+            Reason::YoungAccount => self.young_account_weight,
+            Reason::Denylisted => self.ban_score,
+            Reason::Custom { name: _, weight } => *weight
+        }
+    }
+
+    pub fn verdict(&self, score: u32) -> Option<Verdict> {
+        if score >= self.ban_score {
+            Some(Verdict::Ban)
+        } else if score >= self.hide_score {
+            Some(Verdict::Hide)
+        } else if score >= self.flag_score {
+            Some(Verdict::Flag)
+        } else {
+            None
+        }
+    }
+
+    /// Verdict for an author moderated with `policy`. Exempt authors are never judged.
+    pub fn verdict_with_policy(&self, score: u32, policy: BadgePolicy) -> Option<Verdict> {
+        match policy {
+            BadgePolicy::Exempt => None,
+            BadgePolicy::Relaxed => self.verdict((score as u64 * 100 / self.relaxed_score_percent.max(100) as u64) as u32),
+            BadgePolicy::Normal => self.verdict(score)
+        }
+    }
+
+    /// Whether supporting the stream with `amount` of `currency` is enough to be exempt from checks.
+    /// Amounts can't be compared across currencies, so with a minimum only supports in `support_currency` count.
+    pub fn support_is_enough(&self, amount: u64, currency: &str) -> bool {
+        self.min_support_amount == 0 ||
+        (currency.eq_ignore_ascii_case(&self.support_currency) && amount >= self.min_support_amount as u64)
+    }
+
+    /// When the exemption of an author who supported the stream at `timestamp` ends, `None` if it never does.
+    pub fn support_exemption_end(&self, timestamp: u64) -> Option<u64> {
+        if self.support_exemption_duration == 0 {
+            None
+        } else {
+            Some(timestamp + self.support_exemption_duration as u64)
+        }
+    }
+
+    pub fn support_clears_report(&self) -> bool {
+        self.support_report_policy == SupportReportPolicy::Clear
+    }
+
+    /// Policy for an author with the given badges. With several badges the most lenient policy wins,
+    /// and authors without badges are moderated normally.
+    pub fn badge_policy(&self, badges: &[Badges]) -> BadgePolicy {
+        badges
+            .iter()
+            .map(|badge| match badge {
+                Badges::MEMBER => self.member_policy,
+                Badges::VERIFIED => self.verified_policy,
+                Badges::OWNER => self.owner_policy,
+                Badges::MODERATOR => self.moderator_policy
+            })
+            .fold(BadgePolicy::Normal, BadgePolicy::most_lenient)
     }
 
     pub fn strip_message_from_emoji<'t>(&self, message: &'t str) -> Cow<'t, str> {
@@ -178,6 +1364,162 @@ impl DetectorParams {
         RegexPatterns::new()
     }
 
+    pub fn default_averaging_mode() -> AveragingMode {
+        AveragingMode::Cumulative
+    }
+
+    pub fn default_averaging_window() -> u32 {
+        10
+    }
+
+    pub fn default_burst_message_count() -> u32 {
+        0
+    }
+
+    pub fn default_burst_time_window() -> u32 {
+        3000
+    }
+
+    pub fn default_similarity_metric() -> SimilarityMetric {
+        SimilarityMetric::Jaro
+    }
+
+    pub fn default_similarity_threshold() -> u32 {
+        85
+    }
+
+    pub fn default_retracted_message_count() -> u32 {
+        0
+    }
+
+    pub fn default_retraction_time_threshold() -> u32 {
+        10000
+    }
+
+    pub fn default_copypasta_author_count() -> u32 {
+        0
+    }
+
+    pub fn default_copypasta_time_window() -> u32 {
+        60000
+    }
+
+    pub fn default_max_copypasta_clusters() -> u32 {
+        500
+    }
+
+    pub fn default_author_idle_timeout() -> u32 {
+        1800000
+    }
+
+    pub fn default_max_tracked_authors() -> u32 {
+        10000
+    }
+
+    pub fn default_max_messages_per_author() -> u32 {
+        100
+    }
+
+    pub fn default_slow_mode_weight() -> u32 {
+        50
+    }
+
+    pub fn default_burst_weight() -> u32 {
+        50
+    }
+
+    pub fn default_too_fast_weight() -> u32 {
+        50
+    }
+
+    pub fn default_too_long_weight() -> u32 {
+        50
+    }
+
+    pub fn default_similar_weight() -> u32 {
+        50
+    }
+
+    pub fn default_retracted_message_weight() -> u32 {
+        50
+    }
+
+    pub fn default_copypasta_weight() -> u32 {
+        50
+    }
+
+    pub fn default_young_account_weight() -> u32 {
+        50
+    }
+
+    pub fn default_flag_score() -> u32 {
+        100
+    }
+
+    pub fn default_hide_score() -> u32 {
+        150
+    }
+
+    pub fn default_ban_score() -> u32 {
+        200
+    }
+
+    pub fn default_member_policy() -> BadgePolicy {
+        BadgePolicy::Relaxed
+    }
+
+    pub fn default_verified_policy() -> BadgePolicy {
+        BadgePolicy::Exempt
+    }
+
+    pub fn default_owner_policy() -> BadgePolicy {
+        BadgePolicy::Exempt
+    }
+
+    pub fn default_moderator_policy() -> BadgePolicy {
+        BadgePolicy::Exempt
+    }
+
+    pub fn default_relaxed_score_percent() -> u32 {
+        200
+    }
+
+    pub fn default_min_support_amount() -> u32 {
+        0
+    }
+
+    pub fn default_support_exemption_duration() -> u32 {
+        0
+    }
+
+    pub fn default_support_currency() -> String {
+        "USD".to_string()
+    }
+
+    pub fn default_support_report_policy() -> SupportReportPolicy {
+        SupportReportPolicy::Clear
+    }
+
+    pub fn default_account_age_mode() -> AccountAgeMode {
+        AccountAgeMode::Absolute
+    }
+
+    pub fn default_min_account_age_days() -> u32 {
+        30
+    }
+
+    pub fn default_late_message_policy() -> LateMessagePolicy {
+        LateMessagePolicy::Process
+    }
+
+    pub fn default_max_message_lateness() -> u32 {
+        2000
+    }
+
+    pub fn default_remembered_message_ids() -> u32 {
+        10000
+    }
+
     pub fn avg_time_threshold(&self) -> (u32, TextFieldDescriptor) {
         (self.avg_time_threshold, TextFieldDescriptor::avg_time_threshold())
     }
@@ -186,6 +1528,22 @@ impl DetectorParams {
         (self.avg_time_min_message_count, TextFieldDescriptor::avg_time_min_message_count())
     }
 
+    pub fn averaging_mode(&self) -> (AveragingMode, SelectFieldDescriptor<AveragingMode>) {
+        (self.averaging_mode, SelectFieldDescriptor::averaging_mode())
+    }
+
+    pub fn averaging_window(&self) -> (u32, TextFieldDescriptor) {
+        (self.averaging_window, TextFieldDescriptor::averaging_window())
+    }
+
+    pub fn burst_message_count(&self) -> (u32, TextFieldDescriptor) {
+        (self.burst_message_count, TextFieldDescriptor::burst_message_count())
+    }
+
+    pub fn burst_time_window(&self) -> (u32, TextFieldDescriptor) {
+        (self.burst_time_window, TextFieldDescriptor::burst_time_window())
+    }
+
     pub fn similarity_message_count(&self) -> (u32, TextFieldDescriptor) {
         (self.similarity_message_count, TextFieldDescriptor::similarity_message_count())
     }
@@ -194,6 +1552,14 @@ impl DetectorParams {
         (self.similarity_min_message_length, TextFieldDescriptor::similarity_min_message_length())
     }
 
+    pub fn similarity_metric(&self) -> (SimilarityMetric, SelectFieldDescriptor<SimilarityMetric>) {
+        (self.similarity_metric, SelectFieldDescriptor::similarity_metric())
+    }
+
+    pub fn similarity_threshold(&self) -> (u32, TextFieldDescriptor) {
+        (self.similarity_threshold, TextFieldDescriptor::similarity_threshold())
+    }
+
     pub fn avg_length_threshold(&self) -> (u32, TextFieldDescriptor) {
         (self.avg_length_threshold, TextFieldDescriptor::avg_length_threshold())
     }
@@ -202,6 +1568,138 @@ impl DetectorParams {
         (self.avg_length_message_count, TextFieldDescriptor::avg_length_message_count())
     }
 
+    pub fn retracted_message_count(&self) -> (u32, TextFieldDescriptor) {
+        (self.retracted_message_count, TextFieldDescriptor::retracted_message_count())
+    }
+
+    pub fn retraction_time_threshold(&self) -> (u32, TextFieldDescriptor) {
+        (self.retraction_time_threshold, TextFieldDescriptor::retraction_time_threshold())
+    }
+
+    pub fn copypasta_author_count(&self) -> (u32, TextFieldDescriptor) {
+        (self.copypasta_author_count, TextFieldDescriptor::copypasta_author_count())
+    }
+
+    pub fn copypasta_time_window(&self) -> (u32, TextFieldDescriptor) {
+        (self.copypasta_time_window, TextFieldDescriptor::copypasta_time_window())
+    }
+
+    pub fn max_copypasta_clusters(&self) -> (u32, TextFieldDescriptor) {
+        (self.max_copypasta_clusters, TextFieldDescriptor::max_copypasta_clusters())
+    }
+
+    pub fn author_idle_timeout(&self) -> (u32, TextFieldDescriptor) {
+        (self.author_idle_timeout, TextFieldDescriptor::author_idle_timeout())
+    }
+
+    pub fn max_tracked_authors(&self) -> (u32, TextFieldDescriptor) {
+        (self.max_tracked_authors, TextFieldDescriptor::max_tracked_authors())
+    }
+
+    pub fn max_messages_per_author(&self) -> (u32, TextFieldDescriptor) {
+        (self.max_messages_per_author, TextFieldDescriptor::max_messages_per_author())
+    }
+
+    pub fn late_message_policy(&self) -> (LateMessagePolicy, SelectFieldDescriptor<LateMessagePolicy>) {
+        (self.late_message_policy, SelectFieldDescriptor::late_message_policy())
+    }
+
+    pub fn max_message_lateness(&self) -> (u32, TextFieldDescriptor) {
+        (self.max_message_lateness, TextFieldDescriptor::max_message_lateness())
+    }
+
+    pub fn remembered_message_ids(&self) -> (u32, TextFieldDescriptor) {
+        (self.remembered_message_ids, TextFieldDescriptor::remembered_message_ids())
+    }
+
+    pub fn slow_mode_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.slow_mode_weight, TextFieldDescriptor::slow_mode_weight())
+    }
+
+    pub fn burst_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.burst_weight, TextFieldDescriptor::burst_weight())
+    }
+
+    pub fn too_fast_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.too_fast_weight, TextFieldDescriptor::too_fast_weight())
+    }
+
+    pub fn too_long_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.too_long_weight, TextFieldDescriptor::too_long_weight())
+    }
+
+    pub fn similar_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.similar_weight, TextFieldDescriptor::similar_weight())
+    }
+
+    pub fn retracted_message_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.retracted_message_weight, TextFieldDescriptor::retracted_message_weight())
+    }
+
+    pub fn copypasta_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.copypasta_weight, TextFieldDescriptor::copypasta_weight())
+    }
+
+    pub fn young_account_weight(&self) -> (u32, TextFieldDescriptor) {
+        (self.young_account_weight, TextFieldDescriptor::young_account_weight())
+    }
+
+    pub fn flag_score(&self) -> (u32, TextFieldDescriptor) {
+        (self.flag_score, TextFieldDescriptor::flag_score())
+    }
+
+    pub fn hide_score(&self) -> (u32, TextFieldDescriptor) {
+        (self.hide_score, TextFieldDescriptor::hide_score())
+    }
+
+    pub fn ban_score(&self) -> (u32, TextFieldDescriptor) {
+        (self.ban_score, TextFieldDescriptor::ban_score())
+    }
+
+    pub fn member_policy(&self) -> (BadgePolicy, SelectFieldDescriptor<BadgePolicy>) {
+        (self.member_policy, SelectFieldDescriptor::member_policy())
+    }
+
+    pub fn verified_policy(&self) -> (BadgePolicy, SelectFieldDescriptor<BadgePolicy>) {
+        (self.verified_policy, SelectFieldDescriptor::verified_policy())
+    }
+
+    pub fn owner_policy(&self) -> (BadgePolicy, SelectFieldDescriptor<BadgePolicy>) {
+        (self.owner_policy, SelectFieldDescriptor::owner_policy())
+    }
+
+    pub fn moderator_policy(&self) -> (BadgePolicy, SelectFieldDescriptor<BadgePolicy>) {
+        (self.moderator_policy, SelectFieldDescriptor::moderator_policy())
+    }
+
+    pub fn relaxed_score_percent(&self) -> (u32, TextFieldDescriptor) {
+        (self.relaxed_score_percent, TextFieldDescriptor::relaxed_score_percent())
+    }
+
+    pub fn min_support_amount(&self) -> (u32, TextFieldDescriptor) {
+        (self.min_support_amount, TextFieldDescriptor::min_support_amount())
+    }
+
+    pub fn support_exemption_duration(&self) -> (u32, TextFieldDescriptor) {
+        (self.support_exemption_duration, TextFieldDescriptor::support_exemption_duration())
+    }
+
+    pub fn support_currency(&self) -> &str {
+        &self.support_currency
+    }
+
+    pub fn support_report_policy(&self) -> (SupportReportPolicy, SelectFieldDescriptor<SupportReportPolicy>) {
+        (self.support_report_policy, SelectFieldDescriptor::support_report_policy())
+    }
+
+    pub fn account_age_mode(&self) -> (AccountAgeMode, SelectFieldDescriptor<AccountAgeMode>) {
+        (self.account_age_mode, SelectFieldDescriptor::account_age_mode())
+    }
+
+    pub fn min_account_age_days(&self) -> (u32, TextFieldDescriptor) {
+        (self.min_account_age_days, TextFieldDescriptor::min_account_age_days())
+    }
+
     pub fn min_reg_date_year(&self) -> (u16, TextFieldDescriptor) {
         (self.min_reg_date.year, TextFieldDescriptor::min_reg_date_year())
     }
@@ -211,10 +1709,181 @@ impl DetectorParams {
     }
 
     pub fn min_reg_date_day(&self) -> (u8, TextFieldDescriptor) {
-        (self.min_reg_date.day, TextFieldDescriptor::min_reg_date_day())
+        (self.min_reg_date.day, TextFieldDescriptor::min_reg_date_day_of(self.min_reg_date.year, self.min_reg_date.month))
     }
 
     pub fn min_reg_date_copy(&self) -> RegDate {
         self.min_reg_date.clone()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use crate::{error::DetectorError, reg_date::RegDate};
+    use super::{AccountAgeMode, DetectorParams, DetectorParamsBuilder, ParamViolation, SimilarityMetric};
+
+    #[test]
+    pub fn test_similarity_metric_round_trip() {
+        for metric in SimilarityMetric::all() {
+            let json = serde_json::to_string(&metric).unwrap();
+            let restored: SimilarityMetric = serde_json::from_str(&json).unwrap();
+            assert_eq!(metric, restored);
+        }
+
+        assert_eq!("\"jaroWinkler\"", serde_json::to_string(&SimilarityMetric::JaroWinkler).unwrap());
+        assert_eq!("\"sorensenDice\"", serde_json::to_string(&SimilarityMetric::SorensenDice).unwrap());
+
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        json["similarityMetric"] = "normalizedLevenshtein".into();
+        json["similarityThreshold"] = 70.into();
+        let params: DetectorParams = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!((SimilarityMetric::NormalizedLevenshtein, 70), (params.similarity_metric().0, params.similarity_threshold().0));
+
+        // Configs saved before the metric became configurable fall back to the defaults
+        let object = json.as_object_mut().unwrap();
+        object.remove("similarityMetric");
+        object.remove("similarityThreshold");
+        let params: DetectorParams = serde_json::from_value(json).unwrap();
+        assert_eq!((SimilarityMetric::Jaro, 85), (params.similarity_metric().0, params.similarity_threshold().0));
+    }
+
+    #[test]
+    pub fn test_similarity_metrics() {
+        struct TestCase {
+            metric: SimilarityMetric,
+            threshold: u32,
+            s1: &'static str,
+            s2: &'static str,
+            expected: bool
+        }
+
+        let test_cases = vec![
+            TestCase { metric: SimilarityMetric::Jaro, threshold: 85, s1: "check out my channel", s2: "check out my chanel", expected: true },
+            TestCase { metric: SimilarityMetric::JaroWinkler, threshold: 85, s1: "check out my channel", s2: "check out my chanel", expected: true },
+            TestCase { metric: SimilarityMetric::NormalizedLevenshtein, threshold: 85, s1: "check out my channel", s2: "check out my chanel", expected: true },
+            TestCase { metric: SimilarityMetric::SorensenDice, threshold: 85, s1: "check out my channel", s2: "check out my chanel", expected: true },
+            TestCase { metric: SimilarityMetric::Jaro, threshold: 85, s1: "good morning everyone", s2: "see you tomorrow", expected: false },
+            TestCase { metric: SimilarityMetric::NormalizedLevenshtein, threshold: 95, s1: "check out my channel", s2: "check out my chanel", expected: false },
+        ];
+
+        for test_case in test_cases {
+            let mut params = DetectorParams::get_default();
+            params.similarity_metric = test_case.metric;
+            params.similarity_threshold = test_case.threshold;
+
+            assert_eq!(test_case.expected, params.are_messages_similar(test_case.s1, test_case.s2), "{:?}", test_case.metric);
+        }
+    }
+
+    #[test]
+    pub fn test_account_age_modes() {
+        struct TestCase {
+            account_age_mode: &'static str,
+            reg_date: Option<RegDate>,
+            expected_young: bool
+        }
+
+        // 2023-11-14
+        let timestamp = 1_700_000_000_000;
+
+        let test_cases = vec![
+            TestCase { account_age_mode: "absolute", reg_date: Some(RegDate::new(2020, 10, 1)), expected_young: true },
+            TestCase { account_age_mode: "absolute", reg_date: Some(RegDate::new(2023, 11, 1)), expected_young: true },
+            TestCase { account_age_mode: "absolute", reg_date: Some(RegDate::new(2020, 9, 30)), expected_young: false },
+            TestCase { account_age_mode: "relative", reg_date: Some(RegDate::new(2023, 11, 1)), expected_young: true },
+            TestCase { account_age_mode: "relative", reg_date: Some(RegDate::new(2023, 10, 16)), expected_young: true },
+            TestCase { account_age_mode: "relative", reg_date: Some(RegDate::new(2023, 10, 15)), expected_young: false },
+            TestCase { account_age_mode: "relative", reg_date: Some(RegDate::new(2020, 10, 1)), expected_young: false },
+            TestCase { account_age_mode: "relative", reg_date: None, expected_young: true },
+        ];
+
+        for test_case in test_cases {
+            let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+            json["accountAgeMode"] = test_case.account_age_mode.into();
+            let params: DetectorParams = serde_json::from_value(json).unwrap();
+
+            assert_eq!(30, params.min_account_age_days().0);
+            assert_eq!(
+                test_case.expected_young,
+                params.acc_too_young(test_case.reg_date.as_ref(), timestamp),
+                "{} {:?}", test_case.account_age_mode, test_case.reg_date
+            );
+        }
+
+        assert_eq!(AccountAgeMode::Absolute, DetectorParams::get_default().account_age_mode().0);
+    }
+
+    #[test]
+    pub fn test_params_validation() {
+        struct TestCase {
+            builder: DetectorParamsBuilder,
+            expected_violations: Vec<&'static str>
+        }
+
+        let test_cases = vec![
+            TestCase { builder: DetectorParams::builder(), expected_violations: vec![] },
+            TestCase {
+                builder: DetectorParams::builder()
+                    .burst_message_count(0)
+                    .similarity_metric(SimilarityMetric::SorensenDice)
+                    .min_reg_date(RegDate::new(2024, 2, 29)),
+                expected_violations: vec![]
+            },
+            // 0 turns the rule off
+            TestCase {
+                builder: DetectorParams::builder().avg_time_threshold(0),
+                expected_violations: vec![]
+            },
+            TestCase {
+                builder: DetectorParams::builder().avg_time_threshold(0).burst_time_window(0),
+                expected_violations: vec!["burst_time_window"]
+            },
+            TestCase {
+                builder: DetectorParams::builder().flag_score(200).hide_score(150),
+                expected_violations: vec!["hide_score"]
+            },
+            TestCase {
+                builder: DetectorParams::builder().hide_score(250),
+                expected_violations: vec!["ban_score"]
+            },
+            TestCase {
+                builder: DetectorParams::builder()
+                    .similarity_threshold(100)
+                    .young_account_weight(101)
+                    .ban_score(0)
+                    .min_reg_date(RegDate::new(1999, 12, 31)),
+                expected_violations: vec!["similarity_threshold", "young_account_weight", "ban_score", "min_reg_date_year", "ban_score"]
+            },
+        ];
+
+        for test_case in test_cases {
+            let params = test_case.builder.params;
+            let violations: Vec<String> = match params.validate() {
+                Ok(()) => Vec::new(),
+                Err(violations) => violations.into_iter().map(|violation| violation.field).collect()
+            };
+            assert_eq!(test_case.expected_violations, violations);
+
+            let json = serde_json::to_value(&params).unwrap();
+            let deserialized = serde_json::from_value::<DetectorParams>(json);
+            let built = DetectorParamsBuilder { params }.build();
+            assert_eq!(test_case.expected_violations.is_empty(), deserialized.is_ok());
+            assert_eq!(test_case.expected_violations.is_empty(), built.is_ok());
+            if let Err(DetectorError::InvalidParams(message)) = built {
+                assert!(test_case.expected_violations.iter().all(|field| message.contains(field)), "{}", message);
+            }
+        }
+
+        let violation = ParamViolation { field: "ban_score".to_string(), value: 0, min: 1, max: 1000 };
+        assert_eq!("ban_score is 0, expected a value from 1 to 1000", violation.to_string());
+
+        // Params saved before min_reg_date became an ISO string
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        assert_eq!(serde_json::json!("2020-10-01"), json["minRegDate"]);
+        json["minRegDate"] = serde_json::json!({ "year": 2021, "month": 3, "day": 15 });
+        let params = serde_json::from_value::<DetectorParams>(json.clone()).ok().unwrap();
+        assert_eq!(RegDate::new(2021, 3, 15), params.min_reg_date_copy());
+        json["minRegDate"] = serde_json::json!({ "year": 2021, "month": 2, "day": 30 });
+        assert!(serde_json::from_value::<DetectorParams>(json).is_err());
+    }
+}
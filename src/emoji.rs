@@ -15,7 +15,7 @@ impl RegexPatterns {
     }
 
     pub fn clean_message<'t>(&self, message: &'t str) -> Cow<'t, str> {
-        self.regex.replace_all(&message, "")
+        self.regex.replace_all(message, "")
     }
 }
 
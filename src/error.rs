@@ -0,0 +1,63 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DetectorError {
+    /// `RegDateLoader` failed to load author's registration date
+    LoaderFailure(String),
+    /// Registration date cache file couldn't be read or written
+    CacheFailure(String),
+    /// Config file couldn't be read
+    ConfigFailure(String),
+    /// Imported allow or deny lists are not valid JSON or miss required data
+    InvalidAuthorLists(String),
+    /// Date doesn't exist or isn't a `YYYY-MM-DD` string
+    InvalidDate(String),
+    /// `DetectorParams` contain values that can't be used
+    InvalidParams(String),
+    /// Author sent a message that is older than their previous message
+    TimestampRegression {
+        author: String,
+        last_timestamp: u64,
+        timestamp: u64
+    },
+    /// Chat action is missing required data
+    MalformedAction(String),
+    /// `Snapshot` was written by a newer version of the library
+    UnsupportedSnapshotVersion {
+        version: u32,
+        supported_version: u32
+    },
+}
+
+impl DetectorError {
+    /// Whether repeating the same call later might succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DetectorError::LoaderFailure(_))
+    }
+}
+
+impl fmt::Display for DetectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectorError::LoaderFailure(error) => write!(f, "Couldn't load registration date: {}", error),
+            DetectorError::CacheFailure(error) => write!(f, "Registration date cache failure: {}", error),
+            DetectorError::ConfigFailure(error) => write!(f, "Couldn't read config: {}", error),
+            DetectorError::InvalidAuthorLists(error) => write!(f, "Invalid author lists: {}", error),
+            DetectorError::InvalidDate(error) => write!(f, "Invalid date: {}", error),
+            DetectorError::InvalidParams(error) => write!(f, "Invalid detector params: {}", error),
+            DetectorError::TimestampRegression { author, last_timestamp, timestamp } => write!(
+                f,
+                "Message from {} at {} is older than their previous message at {}",
+                author, timestamp, last_timestamp
+            ),
+            DetectorError::MalformedAction(error) => write!(f, "Malformed chat action: {}", error),
+            DetectorError::UnsupportedSnapshotVersion { version, supported_version } => write!(
+                f,
+                "Snapshot version {} is newer than the supported version {}",
+                version, supported_version
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DetectorError {}
@@ -1,81 +1,253 @@
 use std::collections::HashMap;
-use author_data::Reason;
+pub use author_data::Reason;
+use author_lists::{AuthorLists, ListEntry, ListKind};
+use error::DetectorError;
 use reg_date_loader::{CachedRegDateLoader, RegDateLoader};
-use self::{chat_action::ChatAction, detector_params::DetectorParams, stream_data::StreamData};
+use snapshot::Snapshot;
+use spam_score::SpamScore;
+use self::{action_buffer::ActionBuffer, chat_action::ChatAction, check::Check, detector_params::DetectorParams, stream_data::StreamData};
 
+pub mod author_lists;
 pub mod chat_action;
+pub mod check;
+pub mod config;
 pub mod detector_params;
+pub mod error;
+pub mod preset;
 pub mod reg_date;
 pub mod reg_date_loader;
+pub mod schema;
+pub mod snapshot;
+pub mod spam_score;
+mod action_buffer;
 mod author_data;
+mod copypasta_index;
 mod message_data;
+mod rolling_average;
+mod similarity_index;
 mod stream_data;
+mod support_exemption;
 mod emoji;
 
 pub struct ProcessingResult {
     pub message_id: String,
     pub author: String,
     pub menu_param: String,
-    pub reason: Reason
+    /// The rule that contributed the most to the score
+    pub reason: Reason,
+    pub score: SpamScore
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryStats {
+    pub tracked_authors: usize,
+    pub stored_messages: usize,
+    pub reported_authors: usize,
+    pub superchated_authors: usize,
+    pub copypasta_clusters: usize,
+    /// Rough estimate of the memory held by the detector's state, in bytes.
+    pub estimated_bytes: usize
+}
+
+/// Chat actions that were never processed, counted since the detector was created.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedActions {
+    /// Actions that arrived after newer actions were already processed
+    pub late: u64,
+    /// Messages with an id that was already seen
+    pub duplicate: u64
+}
+
+/// What `Detector::update_params` does with the authors that were already reported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportPolicy {
+    /// Reports stay as they are, new params only apply to new messages
+    Keep,
+    /// Every tracked author is scored again with the new params
+    ReEvaluate,
+    /// Every report is dropped
+    Clear
+}
+
+/// Authors whose report status changed after `Detector::update_params`, sorted by name.
+#[derive(Debug, Default)]
+pub struct ReportsDiff {
+    pub flagged: Vec<String>,
+    pub unflagged: Vec<String>,
+    /// Authors who couldn't be scored again. Their report status didn't change
+    pub errors: Vec<ProcessingError>
+}
+
+/// Returned in place of a `ProcessingResult` when the author of a suspicious
+/// message couldn't be checked, e.g. because their registration date failed to load.
+/// `message_id` is `None` when the failing action was a retraction,
+/// or when the author was caught as a participant of another author's copypasta.
+#[derive(Debug)]
+pub struct ProcessingError {
+    pub message_id: Option<String>,
+    pub author: String,
+    pub error: DetectorError
 }
 
 pub struct Detector {
     stream_data: StreamData,
     params: DetectorParams,
-    // reg_date_loader: CachedRegDateLoader
+    reg_date_loader: CachedRegDateLoader,
+    action_buffer: ActionBuffer
 }
 
 impl Detector {
-    pub fn new(params: DetectorParams) -> Self {
-        // let cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), loader);
+    pub fn new(params: DetectorParams, loader: Box<dyn RegDateLoader>) -> Self {
+        Detector::with_checks(params, loader, check::default_checks())
+    }
+
+    /// Creates a detector that runs `checks`, in order, instead of the built-in rules.
+    /// Use `check::default_checks()` as a base to add new rules to the built-in ones.
+    pub fn with_checks(params: DetectorParams, loader: Box<dyn RegDateLoader>, checks: Vec<Box<dyn Check>>) -> Self {
+        let cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), loader);
+        Detector::with_cached_loader(params, cached_loader, checks)
+    }
+
+    /// Creates a detector with a preconfigured cache, e.g. one that is backed by a file.
+    /// Cache's default registration date is replaced with the one from `params`.
+    pub fn with_cached_loader(params: DetectorParams, mut cached_loader: CachedRegDateLoader, checks: Vec<Box<dyn Check>>) -> Self {
+        cached_loader.update_default_reg_date(params.min_reg_date_copy());
         Detector {
             params,
-            stream_data: StreamData::new()
+            stream_data: StreamData::new(checks),
+            reg_date_loader: cached_loader,
+            action_buffer: ActionBuffer::new()
         }
     }
 
-    pub fn process_messages(&mut self, mut actions: Vec<ChatAction>) -> Result<Vec<ProcessingResult>, String> {
-        actions.sort_unstable_by_key(|action| {
-            match action {
-                ChatAction::Message { 
-                    id: _, 
-                    author: _, 
-                    timestamp, 
-                    content: _, 
-                    badges: _,
-                    context_params: _,
-                } => *timestamp,
-                ChatAction::Support { 
-                    author: _, 
-                    timestamp 
-                } => *timestamp,
-                ChatAction::RetractedMessage {
-                    author: _,
-                    timestamp
-                } => *timestamp
-            }
-        });
+    pub async fn process_messages(&mut self, actions: Vec<ChatAction>) -> Vec<Result<ProcessingResult, ProcessingError>> {
+        let actions = self.action_buffer.push(actions, &self.params);
+
+        self
+            .stream_data
+            .process_messages(&self.params, &mut self.reg_date_loader, actions)
+            .await
+    }
+
+    /// Processes every action that is still held back by `LateMessagePolicy::Reorder`,
+    /// e.g. when the stream is over.
+    pub async fn flush(&mut self) -> Vec<Result<ProcessingResult, ProcessingError>> {
+        let actions = self.action_buffer.flush();
 
         self
             .stream_data
-            .process_messages(&self.params, actions)
+            .process_messages(&self.params, &mut self.reg_date_loader, actions)
+            .await
+    }
+
+    /// Captures the state of the stream, so that it can be saved and restored after a restart.
+    /// Params, checks and registration dates are not included.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = self.stream_data.snapshot();
+        snapshot.action_buffer = self.action_buffer.clone();
+        snapshot
+    }
+
+    pub fn restore(&mut self, mut snapshot: Snapshot) -> Result<(), DetectorError> {
+        snapshot.check_version()?;
+        let action_buffer = std::mem::take(&mut snapshot.action_buffer);
+        self.stream_data.restore(snapshot)?;
+        self.action_buffer = action_buffer;
+        Ok(())
+    }
+
+    /// Writes cached registration dates to the cache file, e.g. before shutting down.
+    pub fn flush_reg_date_cache(&mut self) -> Result<(), DetectorError> {
+        self.reg_date_loader.flush()
+    }
+
+    /// Writes the cache file if its flush interval has passed, see `CachedRegDateLoader::flush_if_due`.
+    pub fn flush_reg_date_cache_if_due(&mut self) -> Result<bool, DetectorError> {
+        self.reg_date_loader.flush_if_due()
     }
 
     pub fn set_slow_mode(&mut self, delay: u32) {
         self.stream_data.set_slow_mode(delay);
     }
 
-    pub fn update_params(&mut self, params: DetectorParams) {
-        // self.reg_date_loader.update_default_reg_date(params.min_reg_date_copy());
+    pub async fn update_params(&mut self, params: DetectorParams, report_policy: ReportPolicy) -> ReportsDiff {
+        self.reg_date_loader.update_default_reg_date(params.min_reg_date_copy());
         self.params = params;
-        self.stream_data.clear_authors_to_report();
+
+        match report_policy {
+            ReportPolicy::Keep => ReportsDiff::default(),
+            ReportPolicy::ReEvaluate => {
+                self.stream_data
+                    .reevaluate_authors(&self.params, &mut self.reg_date_loader)
+                    .await
+            },
+            ReportPolicy::Clear => ReportsDiff {
+                unflagged: self.stream_data.clear_authors_to_report(),
+                ..ReportsDiff::default()
+            }
+        }
+    }
+
+    /// Stops checking the author until `expires_at`, a timestamp in the same units as `ChatAction` timestamps,
+    /// or for good if it's `None`. The author's report is removed.
+    pub fn allow_author(&mut self, author: &str, expires_at: Option<u64>) {
+        self.add_list_entry(author, ListKind::Allow, expires_at);
+    }
+
+    /// Reports every message of the author until `expires_at`, or for good if it's `None`.
+    pub fn deny_author(&mut self, author: &str, expires_at: Option<u64>) {
+        self.add_list_entry(author, ListKind::Deny, expires_at);
+    }
+
+    fn add_list_entry(&mut self, author: &str, list: ListKind, expires_at: Option<u64>) {
+        let entry = ListEntry {
+            author: author.to_string(),
+            list,
+            expires_at,
+            note: String::new()
+        };
+        self.stream_data.add_list_entry(entry, &self.params);
+    }
+
+    /// Removes the author from the allow or deny list. Reports made only because the author was denied are removed too.
+    pub fn remove_listed_author(&mut self, author: &str) -> Option<ListEntry> {
+        self.stream_data.remove_list_entry(author, &self.params)
+    }
+
+    pub fn author_lists(&self) -> &AuthorLists {
+        self.stream_data.author_lists()
+    }
+
+    pub fn export_author_lists(&self) -> String {
+        self.stream_data.author_lists().to_json()
+    }
+
+    /// Adds every entry of the exported lists, replacing the entries of authors that are already listed.
+    /// Nothing is imported if the lists are invalid.
+    pub fn import_author_lists(&mut self, json: &str) -> Result<(), DetectorError> {
+        let lists = AuthorLists::from_json(json)?;
+        for entry in lists.entries() {
+            self.stream_data.add_list_entry(entry, &self.params);
+        }
+        Ok(())
     }
 
     pub fn is_author_spammer(&self, author: &str) -> bool {
         self.stream_data.is_author_spammer(author)
     }
 
-    pub fn get_spammers(&self) -> &HashMap<String, Reason> {
+    pub fn get_spammers(&self) -> &HashMap<String, SpamScore> {
         self.stream_data.get_spammers()
     }
-}
\ No newline at end of file
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        self.stream_data.memory_stats()
+    }
+
+    pub fn dropped_actions(&self) -> DroppedActions {
+        DroppedActions {
+            late: self.action_buffer.late_actions(),
+            duplicate: self.action_buffer.duplicate_messages()
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use serde::{Deserialize, Serialize};
+use crate::detector_params::DetectorParams;
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MessageData {
     message: String,
     count: u16
@@ -11,8 +15,8 @@ impl MessageData {
         }
     }
 
-    pub fn is_message_similar(&self, new_message: &str) -> bool {
-        strsim::jaro(&self.message, new_message) > 0.85
+    pub fn is_message_similar(&self, new_message: &str, detector_params: &DetectorParams) -> bool {
+        detector_params.are_messages_similar(&self.message, new_message)
     }
 
     pub fn reconstruct_message(&mut self, s2: &str) {
@@ -96,6 +100,10 @@ impl MessageData {
     pub fn count(&self) -> u32 {
         self.count as u32
     }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 #[allow(unused_imports)]
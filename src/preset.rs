@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{detector_params::{AccountAgeMode, BadgePolicy, DetectorParams, SupportReportPolicy}, error::DetectorError};
+
+/// Built-in params tuned for different kinds of streams.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Preset {
+    /// Small chats where regulars talk a lot and false positives hurt the most
+    Relaxed,
+    /// Same as `DetectorParams::get_default`
+    Default,
+    /// Big streams with a lot of spam
+    Strict,
+    /// Temporary lockdown during a raid: new accounts and coordinated messages are hit hard
+    RaidLockdown
+}
+
+impl Preset {
+    pub fn all() -> Vec<Preset> {
+        vec![
+            Preset::Relaxed,
+            Preset::Default,
+            Preset::Strict,
+            Preset::RaidLockdown
+        ]
+    }
+
+    pub fn params(&self) -> DetectorParams {
+        let builder = DetectorParams::builder();
+        let builder = match self {
+            Preset::Relaxed => builder
+                .avg_time_threshold(1000)
+                .similarity_message_count(5)
+                .avg_length_threshold(30)
+                .burst_message_count(8)
+                .copypasta_author_count(8)
+                .young_account_weight(30)
+                .flag_score(150)
+                .hide_score(200)
+                .ban_score(300),
+            Preset::Default => builder,
+            Preset::Strict => builder
+                .avg_time_threshold(3000)
+                .similarity_message_count(2)
+                .similarity_threshold(80)
+                .burst_message_count(4)
+                .burst_time_window(5000)
+                .retracted_message_count(2)
+                .copypasta_author_count(3)
+                .support_exemption_duration(3600000)
+                .flag_score(80)
+                .hide_score(120)
+                .ban_score(160),
+            Preset::RaidLockdown => builder
+                .avg_time_threshold(3000)
+                .similarity_message_count(2)
+                .similarity_threshold(80)
+                .burst_message_count(3)
+                .burst_time_window(5000)
+                .retracted_message_count(2)
+                .copypasta_author_count(3)
+                .copypasta_time_window(120000)
+                .young_account_weight(100)
+                .account_age_mode(AccountAgeMode::Relative)
+                .min_account_age_days(7)
+                .member_policy(BadgePolicy::Normal)
+                .support_exemption_duration(600000)
+                .support_report_policy(SupportReportPolicy::Keep)
+                .flag_score(50)
+                .hide_score(100)
+                .ban_score(150)
+        };
+
+        builder
+            .build()
+            .expect("built-in presets are valid")
+    }
+}
+
+/// A preset with some of its fields replaced, e.g.
+/// `{ "preset": "strict", "overrides": { "banScore": 300 } }`.
+/// Overrides use the same names as serialized `DetectorParams`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetConfig {
+    pub preset: Preset,
+    #[serde(default)]
+    pub overrides: Map<String, Value>
+}
+
+impl PresetConfig {
+    pub fn params(&self) -> Result<DetectorParams, DetectorError> {
+        self.preset
+            .params()
+            .with_overrides(&self.overrides)
+    }
+}
+
+impl DetectorParams {
+    pub fn preset(preset: Preset) -> DetectorParams {
+        preset.params()
+    }
+
+    /// Copy of the params with the given serialized fields replaced.
+    /// Fails if a field doesn't exist or the result is invalid.
+    pub fn with_overrides(&self, overrides: &Map<String, Value>) -> Result<DetectorParams, DetectorError> {
+        let mut params = match serde_json::to_value(self) {
+            Ok(Value::Object(params)) => params,
+            _ => return Err(DetectorError::InvalidParams("params can't be serialized".to_string()))
+        };
+
+        for (key, value) in overrides {
+            match params.get_mut(key) {
+                Some(field) => *field = value.clone(),
+                None => return Err(DetectorError::InvalidParams(format!("unknown field {}", key)))
+            }
+        }
+
+        serde_json::from_value(Value::Object(params))
+            .map_err(|error| DetectorError::InvalidParams(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use serde_json::json;
+    use crate::{detector_params::{AccountAgeMode, BadgePolicy, DetectorParams, SupportReportPolicy}, error::DetectorError};
+    use super::{Preset, PresetConfig};
+
+    #[test]
+    pub fn test_presets() {
+        for preset in Preset::all() {
+            let params = preset.params();
+            assert!(params.validate().is_ok(), "preset: {:?}", preset);
+            assert!(params.flag_score().0 <= params.hide_score().0 && params.hide_score().0 <= params.ban_score().0);
+
+            let json = serde_json::to_value(preset).unwrap();
+            assert_eq!(preset, serde_json::from_value(json).unwrap());
+        }
+
+        assert_eq!(
+            serde_json::to_value(DetectorParams::get_default()).unwrap(),
+            serde_json::to_value(DetectorParams::preset(Preset::Default)).unwrap()
+        );
+        assert_eq!("\"raidLockdown\"", serde_json::to_string(&Preset::RaidLockdown).unwrap());
+        assert_eq!(AccountAgeMode::Relative, Preset::RaidLockdown.params().account_age_mode().0);
+    }
+
+    #[test]
+    pub fn test_preset_overrides() {
+        struct TestCase {
+            config: &'static str,
+            expected: Result<(u32, u32), ()>
+        }
+
+        let test_cases = vec![
+            TestCase { config: r#"{"preset": "strict"}"#, expected: Ok((160, 4)) },
+            TestCase { config: r#"{"preset": "strict", "overrides": {"banScore": 300}}"#, expected: Ok((300, 4)) },
+            TestCase { config: r#"{"preset": "relaxed", "overrides": {"banScore": 250, "burstMessageCount": 0}}"#, expected: Ok((250, 0)) },
+            TestCase { config: r#"{"preset": "strict", "overrides": {"banScore": 5000}}"#, expected: Err(()) },
+            TestCase { config: r#"{"preset": "strict", "overrides": {"banScor": 300}}"#, expected: Err(()) },
+            TestCase { config: r#"{"preset": "strict", "overrides": {"banScore": "high"}}"#, expected: Err(()) },
+        ];
+
+        for test_case in test_cases {
+            let config: PresetConfig = serde_json::from_str(test_case.config).unwrap();
+            let params = config.params();
+            match test_case.expected {
+                Ok(expected) => {
+                    let params = params.unwrap();
+                    assert_eq!(expected, (params.ban_score().0, params.burst_message_count().0), "config: {}", test_case.config);
+                },
+                Err(()) => assert!(matches!(params, Err(DetectorError::InvalidParams(_))), "config: {}", test_case.config)
+            }
+        }
+    }
+}
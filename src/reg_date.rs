@@ -1,7 +1,12 @@
-use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use crate::error::DetectorError;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
+/// Calendar date in the proleptic Gregorian calendar, ordered chronologically.
+/// Serialized as an ISO `YYYY-MM-DD` string.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct RegDate {
     pub year: u16,
     pub month: u8,
@@ -9,6 +14,7 @@ pub struct RegDate {
 }
 
 impl RegDate {
+    /// Doesn't check that the date exists, use `try_new` for dates that come from the outside.
     pub fn new(year: u16, month: u8, day: u8) -> Self {
         RegDate {
             year,
@@ -16,33 +22,127 @@ impl RegDate {
             day
         }
     }
+
+    pub fn try_new(year: u16, month: u8, day: u8) -> Result<Self, DetectorError> {
+        if !(1..=12).contains(&month) || day == 0 || day > RegDate::days_in_month(year, month) {
+            return Err(DetectorError::InvalidDate(format!("{:04}-{:02}-{:02} doesn't exist", year, month, day)));
+        }
+
+        Ok(RegDate::new(year, month, day))
+    }
+
+    pub fn is_leap_year(year: u16) -> bool {
+        (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+    }
+
+    /// Number of days in the month, or 0 if there is no such month.
+    pub fn days_in_month(year: u16, month: u8) -> u8 {
+        match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if RegDate::is_leap_year(year) => 29,
+            2 => 28,
+            _ => 0
+        }
+    }
+
+    /// Date of the Unix timestamp in UTC.
+    pub fn from_unix_timestamp(seconds: u64) -> Result<Self, DetectorError> {
+        RegDate::from_days_since_epoch((seconds / 86400) as i64)
+    }
+
+    /// Number of days since 1970-01-01, negative for earlier dates.
+    pub fn days_since_epoch(&self) -> i64 {
+        // Proleptic Gregorian calendar with years starting in March,
+        // so that the leap day is the last day of a year
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    /// Fails if the year doesn't fit in `u16`.
+    pub fn from_days_since_epoch(days: i64) -> Result<Self, DetectorError> {
+        let out_of_range = || DetectorError::InvalidDate(format!("{} days since 1970-01-01 is out of range", days));
+        let days = days.checked_add(719468).ok_or_else(out_of_range)?;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Ok(RegDate {
+            year: u16::try_from(year).map_err(|_| out_of_range())?,
+            month: month as u8,
+            day: day as u8
+        })
+    }
+
+    /// Number of days from this date to `other`, negative if `other` is earlier.
+    pub fn days_between(&self, other: &RegDate) -> i64 {
+        other.days_since_epoch() - self.days_since_epoch()
+    }
+
+    /// Date that is `days` days later, or earlier if `days` is negative.
+    pub fn add_days(&self, days: i64) -> Result<Self, DetectorError> {
+        RegDate::from_days_since_epoch(self.days_since_epoch() + days)
+    }
+}
+
+impl FromStr for RegDate {
+    type Err = DetectorError;
+
+    /// Parses an ISO `YYYY-MM-DD` date.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let is_valid_format = parts.len() == 3 &&
+            parts.iter().zip([4, 2, 2]).all(|(part, len)| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()));
+        if !is_valid_format {
+            return Err(DetectorError::InvalidDate(format!("{:?} is not a YYYY-MM-DD date", s)));
+        }
+
+        // Every part is a short string of digits, so parsing can't fail
+        RegDate::try_new(parts[0].parse().unwrap(), parts[1].parse().unwrap(), parts[2].parse().unwrap())
+    }
 }
 
-impl PartialEq for RegDate {
-    fn eq(&self, other: &Self) -> bool {
-        self.year == other.year &&
-        self.month == other.month &&
-        self.day == other.day
+impl fmt::Display for RegDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
     }
 }
 
-impl PartialOrd for RegDate {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.year > other.year {
-            Some(std::cmp::Ordering::Greater)
-        } else if self.year < other.year {
-            Some(std::cmp::Ordering::Less)
-        } else if self.month > other.month {
-            Some(std::cmp::Ordering::Greater)
-        } else if self.month < other.month {
-            Some(std::cmp::Ordering::Less)
-        } else if self.day > other.day {
-            Some(std::cmp::Ordering::Greater)
-        } else if self.day < other.day {
-            Some(std::cmp::Ordering::Less)
-        } else {
-            Some(std::cmp::Ordering::Equal)
+impl Serialize for RegDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts both ISO strings and `{ year, month, day }` objects written by older versions.
+impl<'de> Deserialize<'de> for RegDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum StoredRegDate {
+            Iso(String),
+            Fields {
+                year: u16,
+                month: u8,
+                day: u8
+            }
         }
+
+        let reg_date = match StoredRegDate::deserialize(deserializer)? {
+            StoredRegDate::Iso(s) => s.parse(),
+            StoredRegDate::Fields { year, month, day } => RegDate::try_new(year, month, day)
+        };
+        reg_date.map_err(de::Error::custom)
     }
 }
 
@@ -54,4 +154,121 @@ impl Default for RegDate {
             day: 1
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use crate::error::DetectorError;
+    use super::RegDate;
+
+    #[test]
+    pub fn test_day_arithmetic() {
+        struct TestCase {
+            unix_timestamp: u64,
+            expected_date: RegDate,
+            expected_days: i64
+        }
+
+        let test_cases = vec![
+            TestCase { unix_timestamp: 0, expected_date: RegDate::new(1970, 1, 1), expected_days: 0 },
+            TestCase { unix_timestamp: 951_782_400, expected_date: RegDate::new(2000, 2, 29), expected_days: 11016 },
+            TestCase { unix_timestamp: 1_601_510_399, expected_date: RegDate::new(2020, 9, 30), expected_days: 18535 },
+            TestCase { unix_timestamp: 1_700_000_000, expected_date: RegDate::new(2023, 11, 14), expected_days: 19675 },
+        ];
+
+        for test_case in test_cases {
+            let date = RegDate::from_unix_timestamp(test_case.unix_timestamp).unwrap();
+            assert_eq!(test_case.expected_date, date);
+            assert_eq!(test_case.expected_days, date.days_since_epoch());
+            assert_eq!(Ok(date), RegDate::from_days_since_epoch(test_case.expected_days));
+        }
+
+        assert_eq!(Ok(RegDate::new(2021, 3, 1)), RegDate::new(2021, 2, 28).add_days(1));
+        assert_eq!(Ok(RegDate::new(2020, 2, 29)), RegDate::new(2020, 3, 1).add_days(-1));
+        assert_eq!(Ok(RegDate::new(2019, 12, 2)), RegDate::new(2020, 1, 1).add_days(-30));
+
+        // Years that don't fit in u16
+        assert_eq!(Ok(RegDate::new(0, 1, 1)), RegDate::from_days_since_epoch(-719528));
+        assert_eq!(Ok(RegDate::new(65535, 12, 31)), RegDate::new(65535, 12, 30).add_days(1));
+        assert!(matches!(RegDate::new(0, 1, 1).add_days(-1), Err(DetectorError::InvalidDate(_))));
+        assert!(matches!(RegDate::new(65535, 12, 31).add_days(1), Err(DetectorError::InvalidDate(_))));
+        assert!(matches!(RegDate::from_unix_timestamp(u64::MAX), Err(DetectorError::InvalidDate(_))));
+        assert!(matches!(RegDate::from_days_since_epoch(i64::MAX), Err(DetectorError::InvalidDate(_))));
+    }
+
+    #[test]
+    pub fn test_leap_years() {
+        for year in 1900..=2400u16 {
+            let expected_leap = if year % 400 == 0 {
+                true
+            } else if year % 100 == 0 {
+                false
+            } else {
+                year % 4 == 0
+            };
+            assert_eq!(expected_leap, RegDate::is_leap_year(year), "year: {}", year);
+            assert_eq!(expected_leap, RegDate::try_new(year, 2, 29).is_ok(), "year: {}", year);
+            assert!(RegDate::try_new(year, 2, 30).is_err());
+
+            let days_in_year: u32 = (1..=12)
+                .map(|month| RegDate::days_in_month(year, month) as u32)
+                .sum();
+            assert_eq!(if expected_leap { 366 } else { 365 }, days_in_year, "year: {}", year);
+            assert_eq!(days_in_year as i64, RegDate::new(year, 1, 1).days_between(&RegDate::new(year + 1, 1, 1)));
+        }
+
+        // Every day in a range that covers all kinds of leap years
+        let mut date = RegDate::new(1896, 1, 1);
+        let mut days = date.days_since_epoch();
+        while date < RegDate::new(2404, 12, 31) {
+            let next = date.add_days(1).unwrap();
+            assert_eq!(days + 1, next.days_since_epoch());
+            assert!(RegDate::try_new(next.year, next.month, next.day).is_ok(), "date: {}", next);
+            assert_eq!(Ok(next.clone()), next.to_string().parse::<RegDate>());
+            assert_eq!(1, date.days_between(&next));
+            assert!(date < next);
+
+            date = next;
+            days += 1;
+        }
+    }
+
+    #[test]
+    pub fn test_iso_dates() {
+        struct TestCase {
+            input: &'static str,
+            expected: Result<RegDate, ()>
+        }
+
+        let test_cases = vec![
+            TestCase { input: "2020-10-01", expected: Ok(RegDate::new(2020, 10, 1)) },
+            TestCase { input: "2024-02-29", expected: Ok(RegDate::new(2024, 2, 29)) },
+            TestCase { input: "2021-02-29", expected: Err(()) },
+            TestCase { input: "2021-02-31", expected: Err(()) },
+            TestCase { input: "2021-13-01", expected: Err(()) },
+            TestCase { input: "2021-00-10", expected: Err(()) },
+            TestCase { input: "2021-1-1", expected: Err(()) },
+            TestCase { input: "+021-01-01", expected: Err(()) },
+            TestCase { input: "2021-01-01T00:00", expected: Err(()) },
+            TestCase { input: "", expected: Err(()) },
+        ];
+
+        for test_case in test_cases {
+            let parsed = test_case.input.parse::<RegDate>();
+            assert_eq!(test_case.expected.is_ok(), parsed.is_ok(), "input: {}", test_case.input);
+            if let Ok(expected) = test_case.expected {
+                assert_eq!(expected, parsed.unwrap());
+                assert_eq!(test_case.input, expected.to_string());
+            } else {
+                assert!(matches!(parsed, Err(DetectorError::InvalidDate(_))));
+            }
+        }
+
+        assert_eq!("\"2020-10-01\"", serde_json::to_string(&RegDate::new(2020, 10, 1)).unwrap());
+        assert_eq!(RegDate::new(2020, 10, 1), serde_json::from_str::<RegDate>("\"2020-10-01\"").unwrap());
+        assert_eq!(RegDate::new(2020, 10, 1), serde_json::from_str::<RegDate>("{\"year\":2020,\"month\":10,\"day\":1}").unwrap());
+        assert!(serde_json::from_str::<RegDate>("\"2021-02-31\"").is_err());
+        assert!(serde_json::from_str::<RegDate>("{\"year\":2021,\"month\":2,\"day\":31}").is_err());
+    }
+}
@@ -1,16 +1,108 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use async_trait::async_trait;
-use crate::reg_date::RegDate;
+use futures::{StreamExt, future::join_all, stream};
+use serde::{Deserialize, Serialize};
+use crate::{error::DetectorError, reg_date::RegDate};
+
+/// How many authors are given to `RegDateLoader::load_many` at once, unless configured otherwise.
+const DEFAULT_BATCH_SIZE: usize = 16;
+/// How many `RegDateLoader::load_many` calls run at the same time, unless configured otherwise.
+const DEFAULT_MAX_CONCURRENT_LOADS: usize = 4;
 
 #[async_trait]
-pub trait RegDateLoader {
-    async fn load(&self, author: &str) -> Result<Option<RegDate>, String>;
+pub trait RegDateLoader: Send + Sync {
+    async fn load(&self, author: &str) -> Result<Option<RegDate>, DetectorError>;
+
+    /// Loads registration dates of several authors, returning one result per author in the same order.
+    /// By default calls `load` for every author concurrently,
+    /// override it if the backend can look up several accounts in one request.
+    async fn load_many(&self, authors: &[String]) -> Vec<Result<Option<RegDate>, DetectorError>> {
+        join_all(authors.iter().map(|author| self.load(author))).await
+    }
+}
+
+/// Where and how often `CachedRegDateLoader` persists its cache.
+pub struct CacheFileConfig {
+    /// JSON lines file, one cached author per line
+    pub path: PathBuf,
+    /// Minimum time between two flushes by `CachedRegDateLoader::flush_if_due`
+    pub flush_interval: Duration
+}
+
+/// How long each kind of loader's result is cached.
+/// `None` means that the result never expires, and a zero duration means that it isn't cached at all.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheTtl {
+    pub found: Option<Duration>,
+    pub not_found: Option<Duration>,
+    pub failed: Option<Duration>
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        CacheTtl {
+            found: None,
+            not_found: None,
+            failed: Some(Duration::from_secs(60))
+        }
+    }
+}
+
+/// Authors without a registration date are cached as such rather than with the default date,
+/// so that a new default applies to them immediately.
+#[derive(Clone)]
+enum CachedResult {
+    Found(RegDate),
+    NotFound,
+    Failed(DetectorError)
+}
+
+#[derive(Clone)]
+struct CacheEntry {
+    result: CachedResult,
+    /// Unix time in milliseconds
+    loaded_at: u64
+}
+
+/// Failures are never written to the file.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheFileLine {
+    author: String,
+    reg_date: Option<RegDate>,
+    /// Unix time in seconds, only in files written before `loaded_at_ms`
+    #[serde(default, skip_serializing)]
+    loaded_at: Option<u64>,
+    #[serde(default)]
+    loaded_at_ms: Option<u64>
+}
+
+impl CacheFileLine {
+    fn loaded_at_ms(&self) -> u64 {
+        self.loaded_at_ms
+            .or_else(|| self.loaded_at.map(|loaded_at| loaded_at.saturating_mul(1000)))
+            .unwrap_or_default()
+    }
+}
+
+struct CacheFile {
+    config: CacheFileConfig,
+    last_flush: u64,
+    dirty: bool
 }
 
 pub struct CachedRegDateLoader {
-    cache: HashMap<String, RegDate>,
+    cache: HashMap<String, CacheEntry>,
     default_reg_date: RegDate,
-    loader: Box<dyn RegDateLoader>
+    loader: Box<dyn RegDateLoader>,
+    ttl: CacheTtl,
+    batch_size: usize,
+    max_concurrent_loads: usize,
+    file: Option<CacheFile>
 }
 
 impl CachedRegDateLoader {
@@ -18,23 +110,569 @@ impl CachedRegDateLoader {
         CachedRegDateLoader {
             cache: HashMap::with_capacity(100),
             default_reg_date,
-            loader
+            loader,
+            ttl: CacheTtl::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_concurrent_loads: DEFAULT_MAX_CONCURRENT_LOADS,
+            file: None
         }
     }
 
-    pub async fn load_reg_date(&mut self, author: &str) -> Result<RegDate, String> {
-        if let Some(reg_date) = self.cache.get(author) {
-            return Ok(reg_date.clone());
+    pub fn with_ttl(mut self, ttl: CacheTtl) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Reloads registration dates that were cached more than `ttl` ago, whether they were found or not.
+    #[deprecated(note = "use `with_ttl`, which also sets how long failures are cached")]
+    pub fn with_entry_ttl(self, ttl: Duration) -> Self {
+        self.with_ttl(CacheTtl {
+            found: Some(ttl),
+            not_found: Some(ttl),
+            ..CacheTtl::default()
+        })
+    }
+
+    /// How many authors are given to `RegDateLoader::load_many` at once.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// How many `RegDateLoader::load_many` calls may run at the same time.
+    pub fn with_max_concurrent_loads(mut self, max_concurrent_loads: usize) -> Self {
+        self.max_concurrent_loads = max_concurrent_loads.max(1);
+        self
+    }
+
+    /// Loads every entry that hasn't expired yet from the file, if it exists.
+    /// The cache is only written back by `flush` and `flush_if_due`, loading never touches the file.
+    /// Lines that can't be parsed, e.g. after a crash in the middle of a write, are skipped.
+    pub fn with_file(mut self, config: CacheFileConfig) -> Result<Self, DetectorError> {
+        let now = unix_now();
+        match fs::File::open(&config.path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line.map_err(|error| cache_failure(&config, error))?;
+                    if let Ok(line) = serde_json::from_str::<CacheFileLine>(&line) {
+                        let loaded_at = line.loaded_at_ms();
+                        let result = match line.reg_date {
+                            Some(reg_date) => CachedResult::Found(reg_date),
+                            None => CachedResult::NotFound
+                        };
+                        let author = line.author;
+                        let entry = CacheEntry { result, loaded_at };
+                        if !self.is_expired(&entry, now) {
+                            self.cache.insert(author, entry);
+                        }
+                    }
+                }
+            },
+            Err(error) if error.kind() == ErrorKind::NotFound => {},
+            Err(error) => return Err(cache_failure(&config, error))
+        }
+
+        self.file = Some(CacheFile {
+            config,
+            last_flush: now,
+            dirty: false
+        });
+        Ok(self)
+    }
+
+    pub async fn load_reg_date(&mut self, author: &str) -> Result<RegDate, DetectorError> {
+        let now = unix_now();
+        if let Some(result) = self.cached_reg_date(author, now) {
+            return result.map(|reg_date| reg_date.unwrap_or_else(|| self.default_reg_date.clone()));
         }
 
-        let reg_date = self.loader.load(author)
-            .await?
-            .unwrap_or_else(|| self.default_reg_date.clone());
-        self.cache.insert(author.to_string(), reg_date.clone());
-        Ok(reg_date)
+        let result = self.loader.load(author).await;
+        let resolved = self.store(author, result, now);
+        resolved.map(|reg_date| reg_date.unwrap_or_else(|| self.default_reg_date.clone()))
+    }
+
+    /// Loads registration dates of several authors at once. Every author is loaded only once,
+    /// no matter how many times they are mentioned. Authors are given to the loader in batches of `batch_size`,
+    /// and at most `max_concurrent_loads` batches are loaded at the same time.
+    /// Unlike `load_reg_date`, returns `None` for authors without a registration date instead of the default one.
+    pub async fn load_reg_dates(&mut self, authors: &[String]) -> HashMap<String, Result<Option<RegDate>, DetectorError>> {
+        let now = unix_now();
+        let mut results = HashMap::with_capacity(authors.len());
+        let mut pending = Vec::new();
+        let mut pending_authors = HashSet::new();
+        for author in authors {
+            if results.contains_key(author) || pending_authors.contains(author) {
+                continue;
+            }
+
+            match self.cached_reg_date(author, now) {
+                Some(result) => {
+                    results.insert(author.clone(), result);
+                },
+                None => {
+                    pending_authors.insert(author);
+                    pending.push(author.clone());
+                }
+            }
+        }
+
+        let loader = &self.loader;
+        let loaded: Vec<(&[String], Vec<_>)> = stream::iter(pending.chunks(self.batch_size))
+            .map(|chunk| async move { (chunk, loader.load_many(chunk).await) })
+            .buffer_unordered(self.max_concurrent_loads)
+            .collect()
+            .await;
+
+        for (chunk, loaded) in loaded {
+            if loaded.len() != chunk.len() {
+                let error = DetectorError::LoaderFailure(format!(
+                    "loader returned {} registration dates for {} authors",
+                    loaded.len(), chunk.len()
+                ));
+                results.extend(chunk.iter().map(|author| (author.clone(), Err(error.clone()))));
+                continue;
+            }
+
+            for (author, result) in chunk.iter().zip(loaded) {
+                let resolved = self.store(author, result, now);
+                results.insert(author.clone(), resolved);
+            }
+        }
+
+        results
+    }
+
+    /// Writes every entry that hasn't expired yet to the cache file, replacing its content.
+    /// Does nothing if the cache isn't backed by a file.
+    pub fn flush(&mut self) -> Result<(), DetectorError> {
+        let now = unix_now();
+        let file = match self.file.as_ref() {
+            Some(file) => file,
+            None => return Ok(())
+        };
+
+        let config = &file.config;
+        let temp_path = config.path.with_extension("tmp");
+        let write = || -> std::io::Result<()> {
+            let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+            for (author, entry) in self.cache.iter() {
+                let reg_date = match &entry.result {
+                    _ if self.is_expired(entry, now) => continue,
+                    CachedResult::Found(reg_date) => Some(reg_date.clone()),
+                    CachedResult::NotFound => None,
+                    CachedResult::Failed(_) => continue
+                };
+
+                let line = CacheFileLine { author: author.clone(), reg_date, loaded_at: None, loaded_at_ms: Some(entry.loaded_at) };
+                serde_json::to_writer(&mut writer, &line)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            fs::rename(&temp_path, &config.path)
+        };
+        let result = write().map_err(|error| cache_failure(config, error));
+
+        if let Some(file) = self.file.as_mut() {
+            file.last_flush = now;
+            if result.is_ok() {
+                file.dirty = false;
+            }
+        }
+        result
+    }
+
+    /// Flushes the cache if entries were loaded since the last successful flush and `flush_interval` has passed,
+    /// returns whether it did. File I/O blocks, so call it regularly from where blocking is fine, not from async code.
+    /// A failed flush is retried after the next interval.
+    pub fn flush_if_due(&mut self) -> Result<bool, DetectorError> {
+        let now = unix_now();
+        let flush_is_due = self.file
+            .as_ref()
+            .is_some_and(|file| file.dirty && now.saturating_sub(file.last_flush) as u128 >= file.config.flush_interval.as_millis());
+        if !flush_is_due {
+            return Ok(false);
+        }
+
+        self.flush().map(|_| true)
+    }
+
+    /// Whether some entries were loaded since the last successful flush.
+    pub fn has_unflushed_entries(&self) -> bool {
+        self.file
+            .as_ref()
+            .is_some_and(|file| file.dirty)
     }
 
     pub fn update_default_reg_date(&mut self, new_default_reg_date: RegDate) {
         self.default_reg_date = new_default_reg_date;
     }
-}
\ No newline at end of file
+
+    /// Forgets cached failures, e.g. once the backend is known to be back up.
+    pub fn clear_failures(&mut self) {
+        self.cache.retain(|_, entry| !matches!(entry.result, CachedResult::Failed(_)));
+    }
+
+    fn cached_reg_date(&self, author: &str, now: u64) -> Option<Result<Option<RegDate>, DetectorError>> {
+        self.cache
+            .get(author)
+            .filter(|entry| !self.is_expired(entry, now))
+            .map(|entry| Self::resolve(&entry.result))
+    }
+
+    /// Caches the loader's result, unless its TTL is zero, and resolves it to a registration date.
+    fn store(&mut self, author: &str, result: Result<Option<RegDate>, DetectorError>, now: u64) -> Result<Option<RegDate>, DetectorError> {
+        let result = match result {
+            Ok(Some(reg_date)) => CachedResult::Found(reg_date),
+            Ok(None) => CachedResult::NotFound,
+            Err(error) => CachedResult::Failed(error)
+        };
+        let resolved = Self::resolve(&result);
+        let entry = CacheEntry { result, loaded_at: now };
+        if self.is_expired(&entry, now) {
+            self.cache.remove(author);
+            return resolved;
+        }
+
+        let persistent = !matches!(entry.result, CachedResult::Failed(_));
+        self.cache.insert(author.to_string(), entry);
+        if let Some(file) = self.file.as_mut().filter(|_| persistent) {
+            file.dirty = true;
+        }
+
+        resolved
+    }
+
+
+    fn resolve(result: &CachedResult) -> Result<Option<RegDate>, DetectorError> {
+        match result {
+            CachedResult::Found(reg_date) => Ok(Some(reg_date.clone())),
+            CachedResult::NotFound => Ok(None),
+            CachedResult::Failed(error) => Err(error.clone())
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry, now: u64) -> bool {
+        let ttl = match entry.result {
+            CachedResult::Found(_) => self.ttl.found,
+            CachedResult::NotFound => self.ttl.not_found,
+            CachedResult::Failed(_) => self.ttl.failed
+        };
+
+        match ttl {
+            Some(ttl) => ttl.is_zero() || now.saturating_sub(entry.loaded_at) as u128 >= ttl.as_millis(),
+            None => false
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn cache_failure(config: &CacheFileConfig, error: impl std::fmt::Display) -> DetectorError {
+    DetectorError::CacheFailure(format!("{}: {}", config.path.display(), error))
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex, atomic::{AtomicUsize, Ordering}};
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use crate::{error::DetectorError, reg_date::RegDate};
+    use super::{CacheFileConfig, CacheTtl, CachedRegDateLoader, RegDateLoader};
+
+    struct CountingLoader {
+        calls: Arc<AtomicUsize>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for CountingLoader {
+        async fn load(&self, _author: &str) -> Result<Option<RegDate>, DetectorError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(RegDate::new(2015, 1, 1)))
+        }
+    }
+
+    fn cache_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("detector_{}_{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn cached_loader(path: &Path, calls: &Arc<AtomicUsize>) -> CachedRegDateLoader {
+        let config = CacheFileConfig { path: path.to_path_buf(), flush_interval: Duration::from_secs(3600) };
+        CachedRegDateLoader::new(RegDate::default(), Box::new(CountingLoader { calls: calls.clone() }))
+            .with_ttl(CacheTtl { found: Some(Duration::from_secs(86400)), ..CacheTtl::default() })
+            .with_file(config)
+            .unwrap()
+    }
+
+    #[test]
+    pub fn test_cache_file() {
+        struct TestCase {
+            file_content: Option<&'static str>,
+            expected_calls_after_restart: usize
+        }
+
+        let test_cases = vec![
+            // Everything that was flushed is reused after a restart
+            TestCase { file_content: None, expected_calls_after_restart: 0 },
+            // Expired and broken entries are loaded again
+            TestCase {
+                file_content: Some("{\"author\":\"a\",\"regDate\":{\"year\":2015,\"month\":1,\"day\":1},\"loadedAt\":0}\n{\"author\":\"b\",\"regD"),
+                expected_calls_after_restart: 2
+            },
+        ];
+
+        for (i, test_case) in test_cases.into_iter().enumerate() {
+            let path = cache_path(&format!("cache_file_{}", i));
+            let calls = Arc::new(AtomicUsize::new(0));
+
+            match test_case.file_content {
+                Some(content) => fs::write(&path, content).unwrap(),
+                None => {
+                    let mut loader = cached_loader(&path, &calls);
+                    block_on(loader.load_reg_date("a")).unwrap();
+                    block_on(loader.load_reg_date("b")).unwrap();
+                    block_on(loader.load_reg_date("a")).unwrap();
+                    assert_eq!(2, calls.load(Ordering::SeqCst));
+                    assert!(loader.has_unflushed_entries());
+                    loader.flush().unwrap();
+                    assert!(!loader.has_unflushed_entries());
+                    calls.store(0, Ordering::SeqCst);
+                }
+            }
+
+            let mut loader = cached_loader(&path, &calls);
+            assert_eq!(RegDate::new(2015, 1, 1), block_on(loader.load_reg_date("a")).unwrap());
+            block_on(loader.load_reg_date("b")).unwrap();
+            assert_eq!(test_case.expected_calls_after_restart, calls.load(Ordering::SeqCst));
+
+            let _ = fs::remove_file(&path);
+        }
+
+        // Loading never writes the file, flushes only happen when they are asked for and due
+        let path = cache_path("cache_file_due");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = CacheFileConfig { path: path.clone(), flush_interval: Duration::ZERO };
+        let mut loader = CachedRegDateLoader::new(RegDate::default(), Box::new(CountingLoader { calls: calls.clone() }))
+            .with_file(config)
+            .unwrap();
+        block_on(loader.load_reg_date("a")).unwrap();
+        block_on(loader.load_reg_dates(&["b".to_string()]));
+        assert!(!path.exists());
+        assert_eq!(Ok(true), loader.flush_if_due());
+        assert!(path.exists());
+        assert_eq!(Ok(false), loader.flush_if_due());
+
+        let mut loader = cached_loader(&path, &calls);
+        block_on(loader.load_reg_date("c")).unwrap();
+        assert_eq!(Ok(false), loader.flush_if_due());
+        let _ = fs::remove_file(&path);
+    }
+
+    struct ScriptedLoader {
+        calls: Arc<AtomicUsize>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for ScriptedLoader {
+        async fn load(&self, author: &str) -> Result<Option<RegDate>, DetectorError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match author {
+                "broken" => Err(DetectorError::LoaderFailure("backend is down".to_string())),
+                "missing" => Ok(None),
+                _ => Ok(Some(RegDate::new(2015, 1, 1)))
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_cache_ttl() {
+        struct TestCase {
+            author: &'static str,
+            ttl: CacheTtl,
+            expected_calls: usize
+        }
+
+        let test_cases = vec![
+            TestCase { author: "broken", ttl: CacheTtl::default(), expected_calls: 1 },
+            TestCase { author: "broken", ttl: CacheTtl { failed: Some(Duration::ZERO), ..CacheTtl::default() }, expected_calls: 3 },
+            TestCase { author: "missing", ttl: CacheTtl::default(), expected_calls: 1 },
+            TestCase { author: "missing", ttl: CacheTtl { not_found: Some(Duration::ZERO), ..CacheTtl::default() }, expected_calls: 3 },
+            TestCase { author: "found", ttl: CacheTtl { found: Some(Duration::from_secs(3600)), ..CacheTtl::default() }, expected_calls: 1 },
+            // Sub-second TTLs aren't rounded down to zero
+            TestCase { author: "found", ttl: CacheTtl { found: Some(Duration::from_millis(500)), ..CacheTtl::default() }, expected_calls: 1 },
+        ];
+
+        for test_case in test_cases {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let mut loader = CachedRegDateLoader::new(RegDate::new(2020, 1, 1), Box::new(ScriptedLoader { calls: calls.clone() }))
+                .with_ttl(test_case.ttl.clone());
+
+            let results: Vec<Result<RegDate, DetectorError>> = (0..3)
+                .map(|_| block_on(loader.load_reg_date(test_case.author)))
+                .collect();
+            assert_eq!(test_case.expected_calls, calls.load(Ordering::SeqCst), "author: {}", test_case.author);
+            assert!(results.windows(2).all(|pair| pair[0] == pair[1]), "author: {}", test_case.author);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    pub fn test_entry_ttl() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut loader = CachedRegDateLoader::new(RegDate::new(2020, 1, 1), Box::new(ScriptedLoader { calls: calls.clone() }))
+            .with_entry_ttl(Duration::from_millis(50));
+
+        for author in ["found", "missing", "found", "missing"] {
+            block_on(loader.load_reg_date(author)).unwrap();
+        }
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        std::thread::sleep(Duration::from_millis(60));
+        for author in ["found", "missing"] {
+            block_on(loader.load_reg_date(author)).unwrap();
+        }
+        assert_eq!(4, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    pub fn test_default_reg_date_is_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut loader = CachedRegDateLoader::new(RegDate::new(2020, 1, 1), Box::new(ScriptedLoader { calls: calls.clone() }));
+
+        assert_eq!(RegDate::new(2020, 1, 1), block_on(loader.load_reg_date("missing")).unwrap());
+        loader.update_default_reg_date(RegDate::new(2021, 6, 1));
+        assert_eq!(RegDate::new(2021, 6, 1), block_on(loader.load_reg_date("missing")).unwrap());
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        assert!(block_on(loader.load_reg_date("broken")).is_err());
+        loader.clear_failures();
+        assert!(block_on(loader.load_reg_date("broken")).is_err());
+        assert_eq!(3, calls.load(Ordering::SeqCst));
+    }
+
+    struct BatchLoader {
+        batches: Arc<Mutex<Vec<Vec<String>>>>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for BatchLoader {
+        async fn load(&self, _author: &str) -> Result<Option<RegDate>, DetectorError> {
+            unreachable!("authors should be loaded in batches")
+        }
+
+        async fn load_many(&self, authors: &[String]) -> Vec<Result<Option<RegDate>, DetectorError>> {
+            self.batches.lock().unwrap().push(authors.to_vec());
+            authors
+                .iter()
+                .map(|_| Ok(Some(RegDate::new(2015, 1, 1))))
+                .collect()
+        }
+    }
+
+    #[test]
+    pub fn test_load_reg_dates() {
+        struct TestCase {
+            authors: Vec<&'static str>,
+            expected_batches: Vec<Vec<&'static str>>
+        }
+
+        let test_cases = vec![
+            // Each author is loaded once, in batches of two
+            TestCase {
+                authors: vec!["a", "b", "a", "c", "b", "d", "e"],
+                expected_batches: vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]
+            },
+            // Cached authors are not loaded again
+            TestCase {
+                authors: vec!["a", "f", "c", "g"],
+                expected_batches: vec![vec!["f", "g"]]
+            },
+        ];
+
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let mut loader = CachedRegDateLoader::new(RegDate::default(), Box::new(BatchLoader { batches: batches.clone() }))
+            .with_batch_size(2);
+
+        for test_case in test_cases {
+            let authors: Vec<String> = test_case.authors
+                .iter()
+                .map(|author| author.to_string())
+                .collect();
+            let reg_dates = block_on(loader.load_reg_dates(&authors));
+
+            assert!(authors.iter().all(|author| reg_dates[author] == Ok(Some(RegDate::new(2015, 1, 1)))));
+            // Batches are loaded concurrently, so they may finish in any order
+            let mut loaded_batches = batches.lock().unwrap().clone();
+            loaded_batches.sort();
+            assert_eq!(test_case.expected_batches, loaded_batches);
+            batches.lock().unwrap().clear();
+        }
+    }
+
+    /// Stays busy for one poll, so that several loads are in flight at once.
+    struct SlowLoader {
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for SlowLoader {
+        async fn load(&self, _author: &str) -> Result<Option<RegDate>, DetectorError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            let mut yielded = false;
+            futures::future::poll_fn(|context| {
+                if yielded {
+                    std::task::Poll::Ready(())
+                } else {
+                    yielded = true;
+                    context.waker().wake_by_ref();
+                    std::task::Poll::Pending
+                }
+            }).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Some(RegDate::new(2015, 1, 1)))
+        }
+    }
+
+    #[test]
+    pub fn test_max_concurrent_loads() {
+        struct TestCase {
+            batch_size: usize,
+            max_concurrent_loads: usize,
+            expected_max_in_flight: usize
+        }
+
+        let test_cases = vec![
+            TestCase { batch_size: 1, max_concurrent_loads: 3, expected_max_in_flight: 3 },
+            TestCase { batch_size: 2, max_concurrent_loads: 3, expected_max_in_flight: 6 },
+            TestCase { batch_size: 4, max_concurrent_loads: 1, expected_max_in_flight: 4 },
+        ];
+
+        for test_case in test_cases {
+            let in_flight = Arc::new(AtomicUsize::new(0));
+            let max_in_flight = Arc::new(AtomicUsize::new(0));
+            let loader = SlowLoader { in_flight: in_flight.clone(), max_in_flight: max_in_flight.clone() };
+            let mut loader = CachedRegDateLoader::new(RegDate::default(), Box::new(loader))
+                .with_batch_size(test_case.batch_size)
+                .with_max_concurrent_loads(test_case.max_concurrent_loads);
+
+            let authors: Vec<String> = (0..20).map(|i| format!("author{}", i)).collect();
+            let reg_dates = block_on(loader.load_reg_dates(&authors));
+
+            assert_eq!(20, reg_dates.len());
+            assert_eq!(test_case.expected_max_in_flight, max_in_flight.load(Ordering::SeqCst), "batch size: {}", test_case.batch_size);
+            assert_eq!(0, in_flight.load(Ordering::SeqCst));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+
+/// Keeps both the average of the last `window` values and an exponential moving average
+/// with a comparable smoothing factor, so that the averaging mode can be switched at any time.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RollingAverage {
+    values: VecDeque<f32>,
+    exponential: Option<f32>,
+}
+
+impl RollingAverage {
+    pub fn new() -> Self {
+        RollingAverage {
+            values: VecDeque::new(),
+            exponential: None,
+        }
+    }
+
+    pub fn add(&mut self, value: f32, window: usize) {
+        self.values.push_back(value);
+        while self.values.len() > window.max(1) {
+            self.values.pop_front();
+        }
+
+        let alpha = 2.0 / (window as f32 + 1.0);
+        self.exponential = Some(match self.exponential {
+            Some(average) => alpha * value + (1.0 - alpha) * average,
+            None => value
+        });
+    }
+
+    pub fn window_average(&self) -> f32 {
+        if self.values.is_empty() {
+            return 0.0;
+        }
+
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+
+    pub fn exponential_average(&self) -> f32 {
+        self.exponential.unwrap_or(0.0)
+    }
+}
+
+#[allow(unused_imports)]
+mod test {
+    use super::RollingAverage;
+
+    #[test]
+    pub fn test_rolling_average() {
+        struct TestCase {
+            input: Vec<f32>,
+            window: usize,
+            expected_window: f32,
+            expected_exponential: f32
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: vec![],
+                window: 3,
+                expected_window: 0.0,
+                expected_exponential: 0.0
+            },
+            TestCase {
+                input: vec![10.0, 20.0, 30.0, 40.0],
+                window: 3,
+                expected_window: 30.0,
+                expected_exponential: 31.25
+            },
+            // A burst after a long calm period dominates the window right away
+            TestCase {
+                input: vec![5000.0, 5000.0, 5000.0, 5000.0, 100.0, 100.0],
+                window: 2,
+                expected_window: 100.0,
+                expected_exponential: 644.4445
+            },
+        ];
+
+        for test_case in test_cases {
+            let mut average = RollingAverage::new();
+            for value in test_case.input {
+                average.add(value, test_case.window);
+            }
+
+            assert_eq!(test_case.expected_window, average.window_average());
+            assert!((test_case.expected_exponential - average.exponential_average()).abs() < 0.01);
+        }
+    }
+}
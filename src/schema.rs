@@ -0,0 +1,326 @@
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::detector_params::{DetectorParams, SelectFieldDescriptor, TextFieldDescriptor};
+
+/// Fields that have no default and must be present in serialized params.
+const REQUIRED_FIELDS: [&str; 7] = [
+    "avg_time_threshold",
+    "avg_time_min_message_count",
+    "similarity_message_count",
+    "similarity_min_message_length",
+    "avg_length_threshold",
+    "avg_length_message_count",
+    "min_reg_date",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum FieldKind {
+    Integer {
+        min: u32,
+        max: u32,
+        /// Increment for UI controls, values between steps are still valid
+        step: u32,
+        /// 0 is accepted even if `min` is greater, and turns the rule off
+        zero_disables: bool
+    },
+    /// One of the serialized names in `options`
+    Select {
+        options: Vec<String>
+    },
+    /// ISO `YYYY-MM-DD` date
+    Date,
+    /// ISO 4217 currency code, e.g. `USD`
+    Currency
+}
+
+/// Everything a settings UI needs to render a field of `DetectorParams`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDescriptor {
+    /// Same as the name in `TextFieldDescriptor` and `SelectFieldDescriptor`
+    pub name: String,
+    /// Name of the field in serialized params
+    pub key: String,
+    pub kind: FieldKind,
+    /// Default value, as it is serialized
+    pub default: Value,
+    pub required: bool,
+    pub label: String,
+    pub description: String
+}
+
+impl DetectorParams {
+    /// Every configurable field, in the order they are declared.
+    pub fn descriptors() -> Vec<FieldDescriptor> {
+        let defaults = DetectorParams::get_default();
+
+        vec![
+            integer_field(defaults.avg_time_threshold()),
+            integer_field(defaults.avg_time_min_message_count()),
+            select_field(defaults.averaging_mode()),
+            integer_field(defaults.averaging_window()),
+            integer_field(defaults.burst_message_count()),
+            integer_field(defaults.burst_time_window()),
+            integer_field(defaults.similarity_message_count()),
+            integer_field(defaults.similarity_min_message_length()),
+            select_field(defaults.similarity_metric()),
+            integer_field(defaults.similarity_threshold()),
+            integer_field(defaults.avg_length_threshold()),
+            integer_field(defaults.avg_length_message_count()),
+            integer_field(defaults.retracted_message_count()),
+            integer_field(defaults.retraction_time_threshold()),
+            integer_field(defaults.copypasta_author_count()),
+            integer_field(defaults.copypasta_time_window()),
+            integer_field(defaults.max_copypasta_clusters()),
+            integer_field(defaults.author_idle_timeout()),
+            integer_field(defaults.max_tracked_authors()),
+            integer_field(defaults.max_messages_per_author()),
+            select_field(defaults.late_message_policy()),
+            integer_field(defaults.max_message_lateness()),
+            integer_field(defaults.remembered_message_ids()),
+            integer_field(defaults.slow_mode_weight()),
+            integer_field(defaults.burst_weight()),
+            integer_field(defaults.too_fast_weight()),
+            integer_field(defaults.too_long_weight()),
+            integer_field(defaults.similar_weight()),
+            integer_field(defaults.retracted_message_weight()),
+            integer_field(defaults.copypasta_weight()),
+            integer_field(defaults.young_account_weight()),
+            integer_field(defaults.flag_score()),
+            integer_field(defaults.hide_score()),
+            integer_field(defaults.ban_score()),
+            select_field(defaults.member_policy()),
+            select_field(defaults.verified_policy()),
+            select_field(defaults.owner_policy()),
+            select_field(defaults.moderator_policy()),
+            integer_field(defaults.relaxed_score_percent()),
+            integer_field(defaults.min_support_amount()),
+            field("support_currency", FieldKind::Currency, json!(defaults.support_currency())),
+            integer_field(defaults.support_exemption_duration()),
+            select_field(defaults.support_report_policy()),
+            select_field(defaults.account_age_mode()),
+            integer_field(defaults.min_account_age_days()),
+            field("min_reg_date", FieldKind::Date, json!(defaults.min_reg_date_copy())),
+        ]
+    }
+
+    /// JSON Schema (draft 2020-12) of serialized params.
+    pub fn json_schema() -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for descriptor in DetectorParams::descriptors() {
+            let mut property = match &descriptor.kind {
+                FieldKind::Integer { min, max, step, zero_disables } => {
+                    let mut property = json!({
+                        "type": "integer",
+                        "minimum": min,
+                        "maximum": max,
+                        // Only a hint for sliders and spinners, values between steps are valid
+                        "x-step": step
+                    });
+                    if *zero_disables && *min > 0 {
+                        property["minimum"] = json!(0);
+                        property["not"] = json!({ "exclusiveMinimum": 0, "exclusiveMaximum": min });
+                    }
+                    property
+                },
+                FieldKind::Select { options } => json!({
+                    "type": "string",
+                    "enum": options
+                }),
+                FieldKind::Date => json!({
+                    "type": "string",
+                    "format": "date"
+                }),
+                FieldKind::Currency => json!({
+                    "type": "string",
+                    "pattern": "^[A-Za-z]{3}$"
+                })
+            };
+            property["title"] = json!(descriptor.label);
+            property["description"] = json!(descriptor.description);
+            property["default"] = descriptor.default;
+
+            if descriptor.required {
+                required.push(descriptor.key.clone());
+            }
+            properties.insert(descriptor.key, property);
+        }
+
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "DetectorParams",
+            "type": "object",
+            "properties": properties,
+            "required": required
+        })
+    }
+}
+
+fn integer_field((default, descriptor): (u32, TextFieldDescriptor)) -> FieldDescriptor {
+    let kind = FieldKind::Integer {
+        min: descriptor.min,
+        max: descriptor.max,
+        step: descriptor.step,
+        zero_disables: descriptor.zero_disables
+    };
+    field(&descriptor.name, kind, json!(default))
+}
+
+fn select_field<T: Serialize>((default, descriptor): (T, SelectFieldDescriptor<T>)) -> FieldDescriptor {
+    let options = descriptor.options
+        .iter()
+        .filter_map(|option| match json!(option) {
+            Value::String(option) => Some(option),
+            _ => None
+        })
+        .collect();
+    field(&descriptor.name, FieldKind::Select { options }, json!(default))
+}
+
+fn field(name: &str, kind: FieldKind, default: Value) -> FieldDescriptor {
+    let (label, description) = field_text(name);
+    FieldDescriptor {
+        name: name.to_string(),
+        key: camel_case(name),
+        kind,
+        default,
+        required: REQUIRED_FIELDS.contains(&name),
+        label: label.to_string(),
+        description: description.to_string()
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut key = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            key.extend(first.to_uppercase());
+            key.push_str(chars.as_str());
+        }
+    }
+    key
+}
+
+fn field_text(name: &str) -> (&'static str, &'static str) {
+    match name {
+        "avg_time_threshold" => ("Average delay", "Authors whose average delay between messages is shorter than this many milliseconds are too fast"),
+        "avg_time_min_message_count" => ("Messages before checking delay", "Average delay is only checked once the author sent this many messages"),
+        "averaging_mode" => ("Averaging mode", "How average delay and message length are calculated from the author's history"),
+        "averaging_window" => ("Averaging window", "Number of recent messages used by the sliding window and exponential averages"),
+        "burst_message_count" => ("Burst size", "Authors who send this many messages within the burst time window are flooding, 0 disables the rule"),
+        "burst_time_window" => ("Burst time window", "Time in milliseconds in which a burst of messages has to be sent"),
+        "similarity_message_count" => ("Similar messages", "Authors who send this many similar messages are repeating themselves"),
+        "similarity_min_message_length" => ("Minimum compared length", "Shorter messages are not compared for similarity"),
+        "similarity_metric" => ("Similarity metric", "String similarity measure used to compare messages"),
+        "similarity_threshold" => ("Similarity threshold", "Messages that are more similar than this percentage are considered the same"),
+        "avg_length_threshold" => ("Average length", "Authors whose average message is at least this many characters long send too long messages"),
+        "avg_length_message_count" => ("Messages before checking length", "Average length is only checked once the author sent this many messages, 0 disables the rule"),
+        "retracted_message_count" => ("Retracted messages", "Authors who quickly retract this many messages are reported, 0 disables the rule"),
+        "retraction_time_threshold" => ("Retraction delay", "Retractions within this many milliseconds after the message count as quick, 0 counts every retraction"),
+        "copypasta_author_count" => ("Copypasta authors", "Number of different authors posting the same message that makes it a copypasta, 0 disables the rule"),
+        "copypasta_time_window" => ("Copypasta time window", "Time in milliseconds after which a copypasta that nobody repeated is forgotten"),
+        "max_copypasta_clusters" => ("Tracked copypastas", "Maximum number of messages compared for copypasta, least recently repeated ones are forgotten first, 0 means no limit"),
+        "author_idle_timeout" => ("Idle timeout", "Authors who were silent for this many milliseconds are forgotten, 0 keeps them forever"),
+        "max_tracked_authors" => ("Tracked authors", "Maximum number of remembered authors, least recently active ones are forgotten first, 0 means no limit"),
+        "max_messages_per_author" => ("Messages per author", "Maximum number of remembered messages per author, 0 means no limit"),
+        "late_message_policy" => ("Late messages", "What happens to chat actions that arrive out of order"),
+        "max_message_lateness" => ("Maximum lateness", "Time in milliseconds that actions are held back to be put in order"),
+        "remembered_message_ids" => ("Remembered message ids", "Number of recent message ids used to drop messages that were delivered twice, 0 keeps every duplicate"),
+        "slow_mode_weight" => ("Slow mode weight", "Score added for breaking slow mode"),
+        "burst_weight" => ("Burst weight", "Score added for a burst of messages"),
+        "too_fast_weight" => ("Too fast weight", "Score added for a short average delay between messages"),
+        "too_long_weight" => ("Too long weight", "Score added for long messages"),
+        "similar_weight" => ("Similar messages weight", "Score added for repeating similar messages"),
+        "retracted_message_weight" => ("Retracted messages weight", "Score added for quickly retracting messages"),
+        "copypasta_weight" => ("Copypasta weight", "Score added for taking part in a copypasta"),
+        "young_account_weight" => ("Young account weight", "Score added for a young account, only when another rule was broken"),
+        "flag_score" => ("Flag score", "Authors with at least this score are flagged"),
+        "hide_score" => ("Hide score", "Authors with at least this score are hidden"),
+        "ban_score" => ("Ban score", "Authors with at least this score are banned"),
+        "member_policy" => ("Members", "How messages from channel members are moderated"),
+        "verified_policy" => ("Verified authors", "How messages from verified authors are moderated"),
+        "owner_policy" => ("Channel owner", "How messages from the channel owner are moderated"),
+        "moderator_policy" => ("Moderators", "How messages from moderators are moderated"),
+        "relaxed_score_percent" => ("Relaxed scores", "Authors with a relaxed policy need this many percent of the usual scores to be flagged, hidden or banned"),
+        "min_support_amount" => ("Minimum support", "Smallest amount, in cents or other minor units of the support currency, that exempts a supporter from checks. Supports in other currencies don't count unless this is 0"),
+        "support_currency" => ("Support currency", "ISO 4217 code of the currency the minimum support is in"),
+        "support_exemption_duration" => ("Support exemption", "For how many milliseconds a supporter is exempt from checks, 0 to exempt them for the rest of the stream"),
+        "support_report_policy" => ("Reports of supporters", "Whether supporting the stream removes the author's report"),
+        "account_age_mode" => ("Account age mode", "Whether accounts are young relative to a fixed date or to the time of the message"),
+        "min_account_age_days" => ("Minimum account age", "In relative mode, accounts younger than this many days are young"),
+        "min_reg_date" => ("Minimum registration date", "In absolute mode, accounts registered on or after this date are young"),
+        _ => ("", "")
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use std::collections::HashSet;
+    use serde_json::{Value, json};
+    use crate::detector_params::DetectorParams;
+    use super::{FieldKind, camel_case};
+
+    #[test]
+    pub fn test_descriptors() {
+        let descriptors = DetectorParams::descriptors();
+        let defaults = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        let defaults = defaults.as_object().unwrap();
+
+        // Every serialized field is described, with its actual default
+        assert_eq!(defaults.len(), descriptors.len());
+        for descriptor in descriptors.iter() {
+            assert_eq!(Some(&descriptor.default), defaults.get(&descriptor.key), "field: {}", descriptor.name);
+            assert!(!descriptor.label.is_empty() && !descriptor.description.is_empty(), "field: {}", descriptor.name);
+        }
+
+        let keys: HashSet<&String> = descriptors.iter().map(|descriptor| &descriptor.key).collect();
+        assert_eq!(descriptors.len(), keys.len());
+
+        let averaging_mode = descriptors.iter().find(|descriptor| descriptor.name == "averaging_mode").unwrap();
+        assert_eq!(FieldKind::Select { options: vec!["cumulative".to_string(), "slidingWindow".to_string(), "exponential".to_string()] }, averaging_mode.kind);
+        assert_eq!("similarityMinMessageLength", camel_case("similarity_min_message_length"));
+    }
+
+    #[test]
+    pub fn test_json_schema() {
+        let schema = DetectorParams::json_schema();
+        let properties = schema["properties"].as_object().unwrap();
+
+        assert_eq!(json!({
+            "type": "integer",
+            "minimum": 1,
+            "maximum": 1000,
+            "x-step": 1,
+            "title": "Ban score",
+            "description": "Authors with at least this score are banned",
+            "default": 200
+        }), properties["banScore"]);
+        // 0 turns the rule off, anything else below the minimum is rejected
+        assert_eq!(json!(0), properties["avgTimeThreshold"]["minimum"]);
+        assert_eq!(json!({ "exclusiveMinimum": 0, "exclusiveMaximum": 1 }), properties["avgTimeThreshold"]["not"]);
+        assert_eq!(json!(100), properties["maxTrackedAuthors"]["x-step"]);
+        assert!(properties.values().all(|property| property.get("multipleOf").is_none()));
+        assert_eq!(json!("date"), properties["minRegDate"]["format"]);
+        assert_eq!(json!("2020-10-01"), properties["minRegDate"]["default"]);
+
+        // Removing a required field makes the params unreadable, removing any other one doesn't
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        for key in properties.keys() {
+            let mut params = serde_json::to_value(DetectorParams::get_default()).unwrap();
+            params.as_object_mut().unwrap().remove(key);
+            let deserialized = serde_json::from_value::<DetectorParams>(params);
+            assert_eq!(required.contains(&key.as_str()), deserialized.is_err(), "field: {}", key);
+        }
+    }
+}
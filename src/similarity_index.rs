@@ -0,0 +1,307 @@
+use std::collections::{HashMap, VecDeque, hash_map::DefaultHasher};
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{detector_params::DetectorParams, message_data::MessageData};
+
+const BANDS: usize = 48;
+const ROWS_PER_BAND: usize = 3;
+const SIGNATURE_SIZE: usize = BANDS * ROWS_PER_BAND;
+const MIN_COLLISIONS: usize = 2;
+/// Up to this many stored messages, every one of them is compared with the new message,
+/// so that the index can't miss a similar message while the history is still cheap to scan.
+/// Kept well below the default `max_messages_per_author`, so that long histories go through the index.
+const LINEAR_SCAN_LIMIT: usize = 16;
+
+type BandKeys = [u64; BANDS];
+
+/// Stores messages in a MinHash LSH index over character bigrams,
+/// so that looking up a similar message doesn't require comparing it with the whole history.
+/// Candidates found through the index are still confirmed with `MessageData::is_message_similar`,
+/// but once there are more than `LINEAR_SCAN_LIMIT` messages, a similar message that shares too few bigrams
+/// with the new one may occasionally be missed.
+///
+/// Messages are identified by the order in which they were stored,
+/// so that the oldest ones can be dropped without renumbering the rest.
+/// Only the messages are serialized, the index is rebuilt when they are deserialized.
+#[derive(Clone)]
+pub struct SimilarityIndex {
+    messages: VecDeque<MessageData>,
+    band_keys: VecDeque<BandKeys>,
+    buckets: HashMap<(usize, u64), Vec<usize>>,
+    first_id: usize,
+}
+
+impl SimilarityIndex {
+    pub fn new() -> Self {
+        SimilarityIndex {
+            messages: VecDeque::new(),
+            band_keys: VecDeque::new(),
+            buckets: HashMap::new(),
+            first_id: 0,
+        }
+    }
+
+    /// Merges the message into the first similar message from the history,
+    /// or stores it as a new one. Returns how many similar messages were sent so far.
+    pub fn add_message(&mut self, content: &str, detector_params: &DetectorParams) -> u32 {
+        let band_keys = Self::band_keys(content);
+
+        if let Some(id) = self.find_similar(content, &band_keys, detector_params) {
+            let position = id - self.first_id;
+            let old_band_keys = self.band_keys[position];
+            self.remove_from_buckets(id, &old_band_keys);
+
+            let message_data = &mut self.messages[position];
+            message_data.reconstruct_message(content);
+            let new_band_keys = Self::band_keys(message_data.message());
+            let count = message_data.count();
+
+            self.insert_into_buckets(id, &new_band_keys);
+            self.band_keys[position] = new_band_keys;
+            return count;
+        }
+
+        let id = self.first_id + self.messages.len();
+        self.messages.push_back(MessageData::new(content.to_string()));
+        self.insert_into_buckets(id, &band_keys);
+        self.band_keys.push_back(band_keys);
+
+        if detector_params.too_many_stored_messages(self.messages.len() as u32) {
+            self.remove_oldest();
+        }
+
+        1
+    }
+
+    fn from_messages(messages: VecDeque<MessageData>) -> Self {
+        let mut similarity_index = SimilarityIndex::new();
+        for message_data in messages {
+            let id = similarity_index.messages.len();
+            let band_keys = Self::band_keys(message_data.message());
+            similarity_index.insert_into_buckets(id, &band_keys);
+            similarity_index.band_keys.push_back(band_keys);
+            similarity_index.messages.push_back(message_data);
+        }
+
+        similarity_index
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Rough estimate of the heap memory used by the stored messages and the index.
+    pub fn estimated_size(&self) -> usize {
+        let messages_size: usize = self.messages
+            .iter()
+            .map(|message_data| message_data.message().len() + size_of::<MessageData>() + size_of::<BandKeys>())
+            .sum();
+        let buckets_size = self.buckets.len() * (size_of::<(usize, u64)>() + size_of::<Vec<usize>>()) +
+            self.messages.len() * BANDS * size_of::<usize>();
+
+        messages_size + buckets_size
+    }
+
+    fn remove_oldest(&mut self) {
+        if let Some(band_keys) = self.band_keys.pop_front() {
+            self.messages.pop_front();
+            self.remove_from_buckets(self.first_id, &band_keys);
+            self.first_id += 1;
+        }
+    }
+
+    fn find_similar(&self, content: &str, band_keys: &BandKeys, detector_params: &DetectorParams) -> Option<usize> {
+        if self.messages.len() <= LINEAR_SCAN_LIMIT {
+            return self.messages
+                .iter()
+                .position(|message_data| message_data.is_message_similar(content, detector_params))
+                .map(|position| self.first_id + position);
+        }
+
+        let mut collisions: HashMap<usize, usize> = HashMap::new();
+        for (band, key) in band_keys.iter().enumerate() {
+            if let Some(bucket) = self.buckets.get(&(band, *key)) {
+                for id in bucket {
+                    *collisions.entry(*id).or_default() += 1;
+                }
+            }
+        }
+
+        let mut candidates: Vec<usize> = collisions
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_COLLISIONS)
+            .map(|(id, _)| id)
+            .collect();
+        candidates.sort_unstable();
+
+        candidates
+            .into_iter()
+            .find(|id| self.messages[*id - self.first_id].is_message_similar(content, detector_params))
+    }
+
+    fn insert_into_buckets(&mut self, id: usize, band_keys: &BandKeys) {
+        for (band, key) in band_keys.iter().enumerate() {
+            self.buckets
+                .entry((band, *key))
+                .or_default()
+                .push(id);
+        }
+    }
+
+    fn remove_from_buckets(&mut self, id: usize, band_keys: &BandKeys) {
+        for (band, key) in band_keys.iter().enumerate() {
+            if let Some(bucket) = self.buckets.get_mut(&(band, *key)) {
+                bucket.retain(|i| *i != id);
+                if bucket.is_empty() {
+                    self.buckets.remove(&(band, *key));
+                }
+            }
+        }
+    }
+
+    fn band_keys(content: &str) -> BandKeys {
+        let signature = Self::signature(content);
+        let mut band_keys = [0; BANDS];
+        for (band_key, rows) in band_keys.iter_mut().zip(signature.chunks(ROWS_PER_BAND)) {
+            let mut hasher = DefaultHasher::new();
+            rows.hash(&mut hasher);
+            *band_key = hasher.finish();
+        }
+
+        band_keys
+    }
+
+    fn signature(content: &str) -> [u64; SIGNATURE_SIZE] {
+        let chars: Vec<char> = content.chars().collect();
+        let mut signature = [u64::MAX; SIGNATURE_SIZE];
+
+        let mut update = |shingle: &[char]| {
+            let mut hasher = DefaultHasher::new();
+            shingle.hash(&mut hasher);
+            let hash = hasher.finish();
+
+            for (i, min_hash) in signature.iter_mut().enumerate() {
+                let seed = (i as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                let permuted = (hash ^ seed).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                let permuted = permuted ^ (permuted >> 31);
+                if permuted < *min_hash {
+                    *min_hash = permuted;
+                }
+            }
+        };
+
+        if chars.len() < 2 {
+            update(&chars);
+        } else {
+            chars.windows(2).for_each(&mut update);
+        }
+
+        signature
+    }
+}
+
+impl Serialize for SimilarityIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.messages.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SimilarityIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        VecDeque::<MessageData>::deserialize(deserializer).map(Self::from_messages)
+    }
+}
+
+#[allow(unused_imports)]
+mod test {
+    use crate::detector_params::DetectorParams;
+    use super::{LINEAR_SCAN_LIMIT, SimilarityIndex};
+
+    #[test]
+    pub fn test_similar_messages_are_merged() {
+        struct TestCase {
+            input: Vec<&'static str>,
+            expected_counts: Vec<u32>
+        }
+
+        let test_cases = vec![
+            TestCase {
+                input: vec!["стрница", "странца", "сраница", "страница"],
+                expected_counts: vec![1, 2, 3, 4]
+            },
+            TestCase {
+                input: vec!["スノムの方が良さげ", "check out my channel", "スノイムのが良さげ", "check out my chanel"],
+                expected_counts: vec![1, 1, 2, 2]
+            },
+            TestCase {
+                input: vec!["good morning everyone", "what game is this", "lol", "see you tomorrow"],
+                expected_counts: vec![1, 1, 1, 1]
+            },
+        ];
+
+        let params = DetectorParams::get_default();
+
+        for test_case in test_cases {
+            let mut index = SimilarityIndex::new();
+            let counts: Vec<u32> = test_case.input
+                .iter()
+                .map(|message| index.add_message(message, &params))
+                .collect();
+
+            assert_eq!(test_case.expected_counts, counts);
+        }
+    }
+
+    #[test]
+    pub fn test_matches_brute_force_scan() {
+        let params = DetectorParams::builder().max_messages_per_author(0).build().unwrap();
+        let words = ["free", "followers", "check", "my", "channel", "hello", "stream", "nice", "play", "when",
+            "subscribe", "giveaway", "today", "link", "bio", "music", "chat", "good", "game", "win"];
+        let mut seed: u64 = 7;
+        let mut next = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+
+        let mut index = SimilarityIndex::new();
+        let mut sent: Vec<String> = Vec::new();
+        let mut found_through_index = 0;
+        for _ in 0..10 * LINEAR_SCAN_LIMIT {
+            // Every other message is a near-duplicate of an earlier one, with typos that change some of its bigrams
+            let mut message: Vec<char> = if !sent.is_empty() && next() % 2 == 0 {
+                sent[next() % sent.len()].chars().collect()
+            } else {
+                (0..4 + next() % 4)
+                    .map(|_| words[next() % words.len()])
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .chars()
+                    .collect()
+            };
+            for _ in 0..1 + next() % 2 {
+                let typo = next() % message.len();
+                message[typo] = 'x';
+            }
+            let message: String = message.into_iter().collect();
+
+            let expected = index.messages
+                .iter()
+                .position(|message_data| message_data.is_message_similar(&message, &params))
+                .map(|position| index.first_id + position);
+            let band_keys = SimilarityIndex::band_keys(&message);
+            let found = index.find_similar(&message, &band_keys, &params);
+            assert_eq!(expected, found, "message: {}", message);
+            if index.len() > LINEAR_SCAN_LIMIT && found.is_some() {
+                found_through_index += 1;
+            }
+
+            index.add_message(&message, &params);
+            sent.push(message);
+        }
+
+        assert!(index.len() > LINEAR_SCAN_LIMIT);
+        assert!(found_through_index > LINEAR_SCAN_LIMIT, "found through the index: {}", found_through_index);
+    }
+}
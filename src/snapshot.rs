@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use serde::{de::{self, MapAccess, SeqAccess, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{action_buffer::ActionBuffer, author_data::AuthorData, author_lists::AuthorLists, copypasta_index::CopypastaIndex, error::DetectorError, spam_score::SpamScore, support_exemption::{self, SupportExemption}};
+
+/// Version of the snapshot format written by this version of the library.
+/// Fields added in later versions must have a serde default, so that older snapshots can still be restored.
+/// Version 2 replaced the set of superchated authors with their `SupportExemption`s.
+/// Version 3 added the actions that are held back to be reordered.
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+/// State of the stream captured by `Detector::snapshot`.
+/// Can be stored with any serde format and passed to `Detector::restore` after a restart.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(remote = "Self", rename_all = "camelCase")]
+pub struct Snapshot {
+    pub(crate) version: u32,
+    pub(crate) authors_to_report: HashMap<String, SpamScore>,
+    #[serde(deserialize_with = "support_exemption::deserialize_exemptions")]
+    pub(crate) superchated_authors: HashMap<String, SupportExemption>,
+    pub(crate) authors: HashMap<String, AuthorData>,
+    pub(crate) copypasta_index: CopypastaIndex,
+    pub(crate) slow_mode: u32,
+    #[serde(default)]
+    pub(crate) author_lists: AuthorLists,
+    #[serde(default)]
+    pub(crate) action_buffer: ActionBuffer,
+}
+
+impl Snapshot {
+    pub(crate) fn new(
+        authors_to_report: HashMap<String, SpamScore>,
+        superchated_authors: HashMap<String, SupportExemption>,
+        authors: HashMap<String, AuthorData>,
+        copypasta_index: CopypastaIndex,
+        slow_mode: u32,
+        author_lists: AuthorLists
+    ) -> Self {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            authors_to_report,
+            superchated_authors,
+            authors,
+            copypasta_index,
+            slow_mode,
+            author_lists,
+            action_buffer: ActionBuffer::new(),
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub(crate) fn check_version(&self) -> Result<(), DetectorError> {
+        if self.version > SNAPSHOT_VERSION {
+            return Err(DetectorError::UnsupportedSnapshotVersion {
+                version: self.version,
+                supported_version: SNAPSHOT_VERSION
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Snapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Snapshot::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Snapshot {
+    /// The version is checked first, a newer snapshot may not have the shape this version expects.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Snapshot", FIELDS, SnapshotVisitor)
+    }
+}
+
+/// Serialized names of the fields, in the order they are written.
+const FIELDS: &[&str] = &["version", "authorsToReport", "superchatedAuthors", "authors", "copypastaIndex", "slowMode", "authorLists", "actionBuffer"];
+
+struct SnapshotVisitor;
+
+impl<'de> Visitor<'de> for SnapshotVisitor {
+    type Value = Snapshot;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a snapshot")
+    }
+
+    /// Formats that write fields in order without their names, such as bincode, always start with the version.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Snapshot, A::Error> {
+        let version: u32 = next_field(&mut seq, 0)?;
+        check_version(version as u64)?;
+
+        let authors_to_report = next_field(&mut seq, 1)?;
+        let superchated_authors = if version < 2 {
+            support_exemption::legacy_exemptions(next_field::<A, HashSet<String>>(&mut seq, 2)?)
+        } else {
+            next_field(&mut seq, 2)?
+        };
+        Ok(Snapshot {
+            version,
+            authors_to_report,
+            superchated_authors,
+            authors: next_field(&mut seq, 3)?,
+            copypasta_index: next_field(&mut seq, 4)?,
+            slow_mode: next_field(&mut seq, 5)?,
+            author_lists: seq.next_element()?.unwrap_or_default(),
+            action_buffer: seq.next_element()?.unwrap_or_default(),
+        })
+    }
+
+    /// Formats that name the fields describe their values too,
+    /// so the values are kept aside until the version is found, wherever it is.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Snapshot, A::Error> {
+        let mut fields = Map::new();
+        while let Some((key, value)) = map.next_entry::<String, Value>()? {
+            fields.insert(key, value);
+        }
+        let version = fields
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| de::Error::missing_field("version"))?;
+        check_version(version)?;
+
+        Snapshot::deserialize(Value::Object(fields)).map_err(de::Error::custom)
+    }
+}
+
+fn next_field<'de, A: SeqAccess<'de>, T: Deserialize<'de>>(seq: &mut A, index: usize) -> Result<T, A::Error> {
+    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(index, &SnapshotVisitor))
+}
+
+fn check_version<E: de::Error>(version: u64) -> Result<(), E> {
+    if version > SNAPSHOT_VERSION as u64 {
+        return Err(E::custom(DetectorError::UnsupportedSnapshotVersion {
+            version: u32::try_from(version).unwrap_or(u32::MAX),
+            supported_version: SNAPSHOT_VERSION
+        }));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{author_data::Reason, detector_params::{BadgePolicy, DetectorParams}};
+
+/// What the host should do with the author, ordered by severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Verdict {
+    Flag,
+    Hide,
+    Ban
+}
+
+/// Sum of the weights of every rule the author broke.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpamScore {
+    pub total: u32,
+    pub verdict: Verdict,
+    pub contributions: Vec<(Reason, u32)>,
+}
+
+impl SpamScore {
+    /// Weighs the broken rules and returns the score, if it's high enough to at least flag the author.
+    pub fn new(reasons: Vec<Reason>, detector_params: &DetectorParams) -> Option<Self> {
+        SpamScore::with_policy(reasons, BadgePolicy::Normal, detector_params)
+    }
+
+    /// Same as `new`, for an author moderated with `policy`.
+    pub fn with_policy(reasons: Vec<Reason>, policy: BadgePolicy, detector_params: &DetectorParams) -> Option<Self> {
+        let contributions: Vec<(Reason, u32)> = reasons
+            .into_iter()
+            .map(|reason| {
+                let weight = detector_params.weight(&reason);
+                (reason, weight)
+            })
+            .filter(|(_, weight)| *weight != 0)
+            .collect();
+        let total = contributions
+            .iter()
+            .map(|(_, weight)| weight)
+            .sum();
+
+        detector_params
+            .verdict_with_policy(total, policy)
+            .map(|verdict| SpamScore {
+                total,
+                verdict,
+                contributions
+            })
+    }
+
+    /// The rule with the biggest contribution to the score.
+    /// On a tie the rule that was checked first wins, so account age never overshadows the rules.
+    pub fn main_reason(&self) -> Reason {
+        self.contributions
+            .iter()
+            .rev()
+            .max_by_key(|(_, weight)| *weight)
+            .map(|(reason, _)| reason.clone())
+            .unwrap_or(Reason::YoungAccount)
+    }
+}
+
+#[allow(unused_imports)]
+mod test {
+    use crate::{author_data::Reason, detector_params::DetectorParams};
+    use super::{SpamScore, Verdict};
+
+    #[test]
+    pub fn test_spam_score() {
+        struct TestCase {
+            reasons: Vec<Reason>,
+            expected_total: u32,
+            expected_verdict: Option<Verdict>
+        }
+
+        let params = DetectorParams::get_default();
+
+        let test_cases = vec![
+            TestCase { reasons: vec![], expected_total: 0, expected_verdict: None },
+            TestCase { reasons: vec![Reason::Similar], expected_total: 50, expected_verdict: None },
+            TestCase { reasons: vec![Reason::Similar, Reason::YoungAccount], expected_total: 100, expected_verdict: Some(Verdict::Flag) },
+            TestCase { reasons: vec![Reason::TooFast(500), Reason::Similar, Reason::YoungAccount], expected_total: 150, expected_verdict: Some(Verdict::Hide) },
+            TestCase { reasons: vec![Reason::SlowMode, Reason::Burst(5), Reason::TooFast(500), Reason::Similar], expected_total: 200, expected_verdict: Some(Verdict::Ban) },
+        ];
+
+        for test_case in test_cases {
+            let reasons_count = test_case.reasons.len();
+            let score = SpamScore::new(test_case.reasons, &params);
+            assert_eq!(test_case.expected_verdict, score.as_ref().map(|score| score.verdict));
+
+            if let Some(score) = score {
+                assert_eq!(test_case.expected_total, score.total);
+                assert_eq!(reasons_count, score.contributions.len());
+                assert!(!matches!(score.main_reason(), Reason::YoungAccount));
+            }
+        }
+    }
+}
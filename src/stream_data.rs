@@ -1,32 +1,59 @@
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::mem::size_of;
 
-use crate::{ProcessingResult, author_data::Reason, reg_date_loader::CachedRegDateLoader};
-use super::{author_data::AuthorData, chat_action::ChatAction, detector_params::DetectorParams};
+use crate::{MemoryStats, ProcessingError, ProcessingResult, ReportsDiff, author_data::Reason, author_lists::{AuthorLists, ListEntry, ListKind}, error::DetectorError, reg_date::RegDate, reg_date_loader::CachedRegDateLoader, snapshot::Snapshot, spam_score::SpamScore, support_exemption::SupportExemption};
+use super::{author_data::AuthorData, chat_action::ChatAction, check::{self, Check}, copypasta_index::CopypastaIndex, detector_params::{BadgePolicy, DetectorParams}};
+
+/// Author who broke at least one rule and whose account age still has to be checked.
+/// `message_id` is `None` for authors caught by a retraction or by another author's copypasta.
+struct Suspect {
+    author: String,
+    message_id: Option<String>,
+    timestamp: u64,
+    menu_param: String,
+    reasons: Vec<Reason>,
+    policy: BadgePolicy
+}
+
+/// Outcome of an action, in the order the actions were processed.
+enum Outcome {
+    Processed(Result<ProcessingResult, ProcessingError>),
+    Suspicious(Suspect)
+}
 
 pub struct StreamData {
-    authors_to_report: HashMap<String, Reason>,
-    superchated_authors: HashSet<String>,
+    authors_to_report: HashMap<String, SpamScore>,
+    superchated_authors: HashMap<String, SupportExemption>,
     authors: HashMap<String, AuthorData>,
+    copypasta_index: CopypastaIndex,
+    checks: Vec<Box<dyn Check>>,
     slow_mode: u32,
+    author_lists: AuthorLists,
 }
 
 impl StreamData {
-    pub fn new() -> Self {
+    pub fn new(checks: Vec<Box<dyn Check>>) -> Self {
        StreamData {
            authors_to_report: HashMap::with_capacity(100),
-           superchated_authors: HashSet::with_capacity(100),
+           superchated_authors: HashMap::with_capacity(100),
            authors: HashMap::with_capacity(500),
+           copypasta_index: CopypastaIndex::new(),
+           checks,
            slow_mode: 0,
+           author_lists: AuthorLists::new(),
        }
     }
 
-    pub fn process_messages(
+    pub async fn process_messages(
         &mut self,
         detector_params: &DetectorParams,
-        // reg_date_loader: &mut CachedRegDateLoader,
+        reg_date_loader: &mut CachedRegDateLoader,
         messages: Vec<ChatAction>
-    ) -> Result<Vec<ProcessingResult>, String> {
-        let mut result = Vec::new();
+    ) -> Vec<Result<ProcessingResult, ProcessingError>> {
+        let mut outcomes = Vec::new();
+        let mut suspects = HashSet::new();
+        let mut latest_timestamp = None;
         for message in messages.into_iter() {
             match message {
                 ChatAction::Message { 
@@ -37,80 +64,1198 @@ impl StreamData {
                     badges ,
                     context_params
                 } => {
-                    if badges.is_some() || self.superchated_authors.contains(&author) {
+                    if id.is_empty() || author.is_empty() {
+                        outcomes.push(Outcome::Processed(Err(ProcessingError {
+                            message_id: Some(id),
+                            author,
+                            error: DetectorError::MalformedAction("message without id or author".to_string())
+                        })));
+                        continue;
+                    }
+
+                    match self.author_lists.status(&author, timestamp) {
+                        Some(ListKind::Allow) => continue,
+                        Some(ListKind::Deny) => {
+                            let score = self.report_denylisted(&author, detector_params);
+                            outcomes.push(Outcome::Processed(Ok(ProcessingResult {
+                                message_id: id,
+                                author,
+                                menu_param: context_params,
+                                reason: score.main_reason(),
+                                score
+                            })));
+                            continue;
+                        },
+                        None => {}
+                    }
+
+                    let badges = badges.unwrap_or_default();
+                    let policy = detector_params.badge_policy(&badges);
+                    if policy == BadgePolicy::Exempt {
                         continue;
                     }
 
-                    if let Some(reason) = self.authors_to_report.get(&author) {
-                        result.push(ProcessingResult {
+                    if let Some(score) = self.authors_to_report.get(&author) {
+                        // Reported authors who keep posting aren't idle, even though their messages aren't recorded
+                        if let Some(author_data) = self.authors.get_mut(&author) {
+                            author_data.mark_seen(timestamp / 1_000);
+                            latest_timestamp = latest_timestamp.max(Some(timestamp / 1_000));
+                        }
+                        outcomes.push(Outcome::Processed(Ok(ProcessingResult {
                             message_id: id,
                             author,
                             menu_param: context_params,
-                            reason: reason.clone()
-                        });
+                            reason: score.main_reason(),
+                            score: score.clone()
+                        })));
                         continue;
                     }
 
                     let timestamp = timestamp / 1_000;
+                    if self.is_supporter(&author, timestamp) {
+                        // The author may still be reported for what they did earlier in this batch
+                        if !detector_params.support_clears_report() && suspects.contains(&author) {
+                            outcomes.push(Outcome::Suspicious(Suspect {
+                                author,
+                                message_id: Some(id),
+                                timestamp,
+                                menu_param: context_params,
+                                reasons: Vec::new(),
+                                policy
+                            }));
+                        }
+                        continue;
+                    }
+                    if let Some(author_data) = self.authors.get(&author) {
+                        if timestamp < author_data.last_message_timestamp() {
+                            let error = DetectorError::TimestampRegression {
+                                author: author.clone(),
+                                last_timestamp: author_data.last_message_timestamp(),
+                                timestamp
+                            };
+                            outcomes.push(Outcome::Processed(Err(ProcessingError {
+                                message_id: Some(id),
+                                author,
+                                error
+                            })));
+                            continue;
+                        }
+                    }
+
+                    latest_timestamp = latest_timestamp.max(Some(timestamp));
                     let cleaned_content = detector_params.strip_message_from_emoji(&content);
 
-                    if let Some(author_data) = self.authors.get_mut(&author) {
-                        if let Some(reason) = author_data.check_message(timestamp, cleaned_content, self.slow_mode, detector_params) {
-                            // let reg_date = reg_date_loader.load_reg_date(&author).await?;
-                            // if detector_params.acc_too_young(&reg_date) {
-                                self.authors_to_report.insert(author.clone(), reason.clone());
-                                result.push(ProcessingResult {
-                                    message_id: id,
-                                    author,
-                                    menu_param: context_params,
-                                    reason
-                                });
-                            // }
-                        }
+                    let copypasta = self.copypasta_index.add_message(&author, timestamp, &cleaned_content, detector_params);
+
+                    let mut reasons = if let Some(author_data) = self.authors.get_mut(&author) {
+                        author_data.set_badges(badges);
+                        let author_state = author_data.record_message(timestamp, &cleaned_content, self.slow_mode, detector_params);
+                        check::run_checks(&self.checks, &author_state, &cleaned_content, detector_params)
                     } else {
-                        let author_data = AuthorData::new(content, timestamp);
-                        self.authors.insert(author, author_data);
+                        let mut author_data = AuthorData::new(content, timestamp, detector_params);
+                        author_data.set_badges(badges);
+                        self.authors.insert(author.clone(), author_data);
+                        Vec::new()
+                    };
+
+                    if let Some((cluster_size, _)) = &copypasta {
+                        reasons.push(Reason::Copypasta(*cluster_size));
+                    }
+
+                    // A message from an author who is already suspected in this batch
+                    // has to be reported too if the author turns out to be a spammer
+                    if !reasons.is_empty() || suspects.contains(&author) {
+                        suspects.insert(author.clone());
+                        outcomes.push(Outcome::Suspicious(Suspect {
+                            author: author.clone(),
+                            message_id: Some(id),
+                            timestamp,
+                            menu_param: context_params,
+                            reasons,
+                            policy
+                        }));
+                    }
+
+                    if let Some((cluster_size, participants)) = copypasta {
+                        for participant in participants {
+                            if participant == author ||
+                                self.authors_to_report.contains_key(&participant) ||
+                                self.is_supporter(&participant, timestamp) ||
+                                self.author_lists.status(&participant, timestamp * 1_000).is_some() {
+                                continue;
+                            }
+
+                            let policy = self.authors
+                                .get(&participant)
+                                .map(|author_data| detector_params.badge_policy(author_data.badges()))
+                                .unwrap_or_default();
+                            suspects.insert(participant.clone());
+                            outcomes.push(Outcome::Suspicious(Suspect {
+                                author: participant,
+                                message_id: None,
+                                timestamp,
+                                menu_param: String::new(),
+                                reasons: vec![Reason::Copypasta(cluster_size)],
+                                policy
+                            }));
+                        }
                     }
                 },
-                ChatAction::Support { 
-                    author ,
-                    timestamp: _,
+                ChatAction::Support {
+                    author,
+                    timestamp,
+                    amount,
+                    currency
                 } => {
-                    self.authors_to_report.remove(&author);
-                    self.superchated_authors.insert(author);
+                    if !detector_params.support_is_enough(amount, &currency) {
+                        continue;
+                    }
+
+                    if detector_params.support_clears_report() {
+                        self.authors_to_report.remove(&author);
+                    }
+
+                    let timestamp = timestamp / 1_000;
+                    let exemption = SupportExemption::new(timestamp, detector_params.support_exemption_end(timestamp), amount, currency);
+                    match self.superchated_authors.entry(author) {
+                        Entry::Occupied(mut entry) => entry.get_mut().extend(exemption),
+                        Entry::Vacant(entry) => {
+                            entry.insert(exemption);
+                        }
+                    }
                 },
                 ChatAction::RetractedMessage {
                     author,
-                    timestamp: _,
+                    timestamp,
                 } => {
-                    if let Some(_) = self.authors_to_report.get(&author) {
+                    if author.is_empty() {
+                        outcomes.push(Outcome::Processed(Err(ProcessingError {
+                            message_id: None,
+                            author,
+                            error: DetectorError::MalformedAction("retraction without author".to_string())
+                        })));
+                        continue;
+                    }
+
+                    if self.author_lists.status(&author, timestamp).is_some() {
                         continue;
                     }
 
-                    // let reg_date = reg_date_loader.load_reg_date(&author).await?;
-                    // if detector_params.acc_too_young(&reg_date) {
-                        // self.authors_to_report.insert(author, Reason::RetractedMessage);
-                    // }
+                    let timestamp = timestamp / 1_000;
+                    if self.authors_to_report.contains_key(&author) || self.is_supporter(&author, timestamp) {
+                        continue;
+                    }
+
+                    let (reasons, policy) = match self.authors.get_mut(&author) {
+                        Some(author_data) => (
+                            author_data.check_retraction(timestamp, detector_params).into_iter().collect(),
+                            detector_params.badge_policy(author_data.badges())
+                        ),
+                        None => (Vec::new(), BadgePolicy::Normal)
+                    };
+
+                    if !reasons.is_empty() {
+                        suspects.insert(author.clone());
+                        outcomes.push(Outcome::Suspicious(Suspect {
+                            author,
+                            message_id: None,
+                            timestamp,
+                            menu_param: String::new(),
+                            reasons,
+                            policy
+                        }));
+                    }
                 }
             }
         }
 
-        Ok(result)
+        if let Some(now) = latest_timestamp {
+            self.evict_authors(now, detector_params);
+        }
+
+        // Registration dates are only loaded for authors who broke at least one rule,
+        // all at once, so that a raid doesn't wait for every account to be looked up in turn.
+        let reg_dates = if detector_params.weight(&Reason::YoungAccount) != 0 {
+            let suspects: Vec<String> = suspects.into_iter().collect();
+            reg_date_loader.load_reg_dates(&suspects).await
+        } else {
+            HashMap::new()
+        };
+
+        outcomes
+            .into_iter()
+            .filter_map(|outcome| match outcome {
+                Outcome::Processed(processed) => Some(processed),
+                Outcome::Suspicious(suspect) => self.judge_suspect(suspect, &reg_dates, detector_params)
+            })
+            .collect()
+    }
+
+    /// Reports the suspect if their score is high enough.
+    /// Authors who were reported earlier in the same batch are not scored again.
+    fn judge_suspect(
+        &mut self,
+        suspect: Suspect,
+        reg_dates: &HashMap<String, Result<Option<RegDate>, DetectorError>>,
+        detector_params: &DetectorParams
+    ) -> Option<Result<ProcessingResult, ProcessingError>> {
+        let Suspect { author, message_id, timestamp, menu_param, reasons, policy } = suspect;
+        let score = match self.authors_to_report.get(&author) {
+            Some(score) => score.clone(),
+            None if self.support_covers(&author, timestamp, detector_params) => return None,
+            None => match Self::score_author(reasons, policy, reg_dates.get(&author), timestamp, detector_params) {
+                Ok(Some(score)) => {
+                    self.authors_to_report.insert(author.clone(), score.clone());
+                    score
+                },
+                Ok(None) => return None,
+                Err(error) => return Some(Err(ProcessingError { message_id, author, error }))
+            }
+        };
+
+        message_id.map(|message_id| Ok(ProcessingResult {
+            message_id,
+            author,
+            menu_param,
+            reason: score.main_reason(),
+            score
+        }))
+    }
+
+    /// Adds author's account age at `timestamp` to the rules they broke and weighs them.
+    fn score_author(
+        mut reasons: Vec<Reason>,
+        policy: BadgePolicy,
+        reg_date: Option<&Result<Option<RegDate>, DetectorError>>,
+        timestamp: u64,
+        detector_params: &DetectorParams,
+    ) -> Result<Option<SpamScore>, DetectorError> {
+        if reasons.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(reg_date) = reg_date {
+            let reg_date = reg_date.as_ref().map_err(Clone::clone)?;
+            if detector_params.acc_too_young(reg_date.as_ref(), timestamp) {
+                reasons.push(Reason::YoungAccount);
+            }
+        }
+
+        Ok(SpamScore::with_policy(reasons, policy, detector_params))
+    }
+
+    /// Adds `Reason::Denylisted` to the author's report, so that an author who was only flagged before is banned.
+    fn report_denylisted(&mut self, author: &str, detector_params: &DetectorParams) -> SpamScore {
+        let mut reasons = self.reported_reasons(author);
+        if !reasons.iter().any(|reason| matches!(reason, Reason::Denylisted)) {
+            reasons.push(Reason::Denylisted);
+        }
+
+        let score = SpamScore::new(reasons, detector_params).expect("denylisted authors are banned");
+        self.authors_to_report.insert(author.to_string(), score.clone());
+        score
+    }
+
+    /// Takes `Reason::Denylisted` out of the report of an author who is no longer denied,
+    /// the report stays if the other reasons are enough for it.
+    fn unreport_denylisted(&mut self, author: &str, detector_params: &DetectorParams) {
+        let reasons: Vec<Reason> = self.reported_reasons(author)
+            .into_iter()
+            .filter(|reason| !matches!(reason, Reason::Denylisted))
+            .collect();
+        match SpamScore::new(reasons, detector_params) {
+            Some(score) => {
+                self.authors_to_report.insert(author.to_string(), score);
+            },
+            None => {
+                self.authors_to_report.remove(author);
+            }
+        }
+    }
+
+    fn reported_reasons(&self, author: &str) -> Vec<Reason> {
+        self.authors_to_report
+            .get(author)
+            .map(|score| score.contributions.iter().map(|(reason, _)| reason.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds the author to a list, replacing their previous entry.
+    /// Allowed authors lose their report and denied authors are reported right away.
+    pub fn add_list_entry(&mut self, entry: ListEntry, detector_params: &DetectorParams) {
+        let author = entry.author.clone();
+        let list = entry.list;
+        self.author_lists.insert(entry);
+        match list {
+            ListKind::Allow => {
+                self.authors_to_report.remove(&author);
+            },
+            ListKind::Deny => {
+                self.report_denylisted(&author, detector_params);
+            }
+        }
+    }
+
+    pub fn remove_list_entry(&mut self, author: &str, detector_params: &DetectorParams) -> Option<ListEntry> {
+        let entry = self.author_lists.remove(author)?;
+        if entry.list == ListKind::Deny {
+            self.unreport_denylisted(author, detector_params);
+        }
+        Some(entry)
+    }
+
+    pub fn author_lists(&self) -> &AuthorLists {
+        &self.author_lists
+    }
+
+    fn is_supporter(&self, author: &str, timestamp: u64) -> bool {
+        self.superchated_authors
+            .get(author)
+            .is_some_and(|exemption| exemption.is_active(timestamp))
+    }
+
+    /// Whether the author's support keeps them from being reported for what they did at `timestamp`.
+    /// Unless reports are kept, support also covers what the author did before it.
+    fn support_covers(&self, author: &str, timestamp: u64, detector_params: &DetectorParams) -> bool {
+        self.superchated_authors
+            .get(author)
+            .is_some_and(|exemption| {
+                exemption.is_active(timestamp) ||
+                (timestamp < exemption.since && detector_params.support_clears_report())
+            })
+    }
+
+    /// Forgets authors who were silent for too long, and then the least recently active ones
+    /// if there are still more authors than allowed. Their reports are forgotten with them, unless they are denied.
+    /// Expired support exemptions and list entries are forgotten too.
+    fn evict_authors(&mut self, now: u64, detector_params: &DetectorParams) {
+        for entry in self.author_lists.remove_expired(now * 1_000) {
+            if entry.list == ListKind::Deny {
+                self.unreport_denylisted(&entry.author, detector_params);
+            }
+        }
+        self.superchated_authors.retain(|_, exemption| !exemption.is_expired(now));
+
+        self.authors.retain(|_, author_data| {
+            !detector_params.author_is_idle(now.saturating_sub(author_data.last_seen_timestamp()))
+        });
+
+        if detector_params.too_many_tracked_authors(self.authors.len()) {
+            let mut authors: Vec<(u64, String)> = self.authors
+                .iter()
+                .map(|(author, author_data)| (author_data.last_seen_timestamp(), author.clone()))
+                .collect();
+            let evicted_count = authors.len() - detector_params.max_tracked_authors_count();
+            authors.select_nth_unstable(evicted_count - 1);
+            for (_, author) in authors.into_iter().take(evicted_count) {
+                self.authors.remove(&author);
+            }
+        }
+
+        let authors = &self.authors;
+        let author_lists = &self.author_lists;
+        self.authors_to_report.retain(|author, _| {
+            authors.contains_key(author) || author_lists.status(author, now * 1_000) == Some(ListKind::Deny)
+        });
+    }
+
+    pub fn memory_stats(&self) -> MemoryStats {
+        let stored_messages = self.authors
+            .values()
+            .map(|author_data| author_data.stored_messages())
+            .sum();
+        let authors_size: usize = self.authors
+            .iter()
+            .map(|(author, author_data)| author.len() + author_data.estimated_size())
+            .sum();
+        let reports_size: usize = self.authors_to_report
+            .iter()
+            .map(|(author, score)| author.len() + size_of::<SpamScore>() + score.contributions.len() * size_of::<(Reason, u32)>())
+            .sum();
+        let superchated_size: usize = self.superchated_authors
+            .iter()
+            .map(|(author, exemption)| author.len() + exemption.estimated_size())
+            .sum();
+
+        MemoryStats {
+            tracked_authors: self.authors.len(),
+            stored_messages,
+            reported_authors: self.authors_to_report.len(),
+            superchated_authors: self.superchated_authors.len(),
+            copypasta_clusters: self.copypasta_index.len(),
+            estimated_bytes: authors_size + reports_size + superchated_size + self.copypasta_index.estimated_size()
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(
+            self.authors_to_report.clone(),
+            self.superchated_authors.clone(),
+            self.authors.clone(),
+            self.copypasta_index.clone(),
+            self.slow_mode,
+            self.author_lists.clone()
+        )
+    }
+
+    /// Replaces the whole state with the snapshot, keeping the checks.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), DetectorError> {
+        snapshot.check_version()?;
+
+        self.authors_to_report = snapshot.authors_to_report;
+        self.superchated_authors = snapshot.superchated_authors;
+        self.authors = snapshot.authors;
+        self.copypasta_index = snapshot.copypasta_index;
+        self.slow_mode = snapshot.slow_mode;
+        self.author_lists = snapshot.author_lists;
+        Ok(())
     }
 
     pub fn set_slow_mode(&mut self, new_delay: u32) {
         self.slow_mode = new_delay;
     }
 
-    pub fn clear_authors_to_report(&mut self) {
-        self.authors_to_report.clear();
+    /// Returns every author that was reported.
+    pub fn clear_authors_to_report(&mut self) -> Vec<String> {
+        let mut authors: Vec<String> = self.authors_to_report
+            .drain()
+            .map(|(author, _)| author)
+            .collect();
+        authors.sort();
+        authors
+    }
+
+    /// Scores every tracked author again from their stored statistics, reporting and unreporting them as needed.
+    /// Copypasta can't be detected again, so it keeps contributing to the score of authors it was reported for.
+    /// Denied authors stay reported, and so do authors whose registration date failed to load.
+    /// Recent timestamps were trimmed to the old `burst_message_count`, so raising it can't turn up bursts that weren't kept.
+    pub async fn reevaluate_authors(
+        &mut self,
+        detector_params: &DetectorParams,
+        reg_date_loader: &mut CachedRegDateLoader
+    ) -> ReportsDiff {
+        let mut suspects = Vec::with_capacity(self.authors.len());
+        for (author, author_data) in self.authors.iter() {
+            let timestamp = author_data.last_message_timestamp();
+            if self.support_covers(author, timestamp, detector_params) || self.author_lists.status(author, timestamp * 1_000).is_some() {
+                continue;
+            }
+
+            let author_state = author_data.current_state(self.slow_mode, detector_params);
+            let mut reasons = check::run_checks(&self.checks, &author_state, author_data.last_message(), detector_params);
+            reasons.extend(author_data.retraction_reason(detector_params));
+            if let Some(score) = self.authors_to_report.get(author) {
+                let copypasta = score.contributions
+                    .iter()
+                    .filter(|(reason, _)| matches!(reason, Reason::Copypasta(_)))
+                    .map(|(reason, _)| reason.clone());
+                reasons.extend(copypasta);
+            }
+
+            let policy = detector_params.badge_policy(author_data.badges());
+            suspects.push((author.clone(), reasons, policy, author_data.last_message_timestamp()));
+        }
+
+        let reg_dates = if detector_params.weight(&Reason::YoungAccount) != 0 {
+            let authors: Vec<String> = suspects
+                .iter()
+                .filter(|(_, reasons, _, _)| !reasons.is_empty())
+                .map(|(author, _, _, _)| author.clone())
+                .collect();
+            reg_date_loader.load_reg_dates(&authors).await
+        } else {
+            HashMap::new()
+        };
+
+        let mut diff = ReportsDiff::default();
+        for (author, reasons, policy, timestamp) in suspects {
+            match Self::score_author(reasons, policy, reg_dates.get(&author), timestamp, detector_params) {
+                Ok(Some(score)) => {
+                    if self.authors_to_report.insert(author.clone(), score).is_none() {
+                        diff.flagged.push(author);
+                    }
+                },
+                Ok(None) => {
+                    if self.authors_to_report.remove(&author).is_some() {
+                        diff.unflagged.push(author);
+                    }
+                },
+                Err(error) => {
+                    diff.errors.push(ProcessingError {
+                        message_id: None,
+                        author,
+                        error
+                    });
+                }
+            }
+        }
+
+        diff.flagged.sort();
+        diff.unflagged.sort();
+        diff
     }
 
     pub fn is_author_spammer(&self, author: &str) -> bool {
         self.authors_to_report.contains_key(author)
     }
 
-    pub fn get_spammers(&self) -> &HashMap<String, Reason> {
+    pub fn get_spammers(&self) -> &HashMap<String, SpamScore> {
         &self.authors_to_report
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+#[allow(unused_imports)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use crate::{author_data::Reason, author_lists::{ListEntry, ListKind}, chat_action::{Badges, ChatAction}, check::default_checks, detector_params::{BadgePolicy, DetectorParams, SupportReportPolicy}, error::DetectorError, reg_date::RegDate, reg_date_loader::{CachedRegDateLoader, RegDateLoader}, snapshot::{Snapshot, SNAPSHOT_VERSION}, spam_score::{SpamScore, Verdict}};
+    use super::StreamData;
+
+    struct TestLoader {
+        reg_dates: HashMap<String, Result<Option<RegDate>, DetectorError>>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for TestLoader {
+        async fn load(&self, author: &str) -> Result<Option<RegDate>, DetectorError> {
+            self.reg_dates
+                .get(author)
+                .cloned()
+                .unwrap_or(Ok(None))
+        }
+    }
+
+    fn spam_from(author: &str) -> Vec<ChatAction> {
+        (0..3u64)
+            .map(|i| ChatAction::Message {
+                id: format!("{}_{}", author, i),
+                author: author.to_string(),
+                timestamp: (i + 1) * 10_000_000,
+                content: "buy cheap followers here".to_string(),
+                badges: None,
+                context_params: String::new()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_reg_date_is_consulted() {
+        struct TestCase {
+            author: &'static str,
+            expected_reported: bool,
+            expected_error: bool
+        }
+
+        let mut reg_dates = HashMap::new();
+        reg_dates.insert("young".to_string(), Ok(Some(RegDate::new(2021, 1, 1))));
+        reg_dates.insert("old".to_string(), Ok(Some(RegDate::new(2015, 1, 1))));
+        reg_dates.insert("broken".to_string(), Err(DetectorError::LoaderFailure("backend is down".to_string())));
+        let loader = TestLoader { reg_dates };
+
+        let params = DetectorParams::get_default();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(loader));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let test_cases = vec![
+            TestCase { author: "young", expected_reported: true, expected_error: false },
+            TestCase { author: "old", expected_reported: false, expected_error: false },
+            TestCase { author: "unknown", expected_reported: true, expected_error: false },
+            TestCase { author: "broken", expected_reported: false, expected_error: true },
+        ];
+
+        for test_case in test_cases {
+            let results = block_on(stream_data.process_messages(&params, &mut cached_loader, spam_from(test_case.author)));
+            let reported = results.iter().any(|r| r.is_ok());
+            let errored = results.iter().any(|r| r.is_err());
+
+            assert_eq!(test_case.expected_reported, reported, "author: {}", test_case.author);
+            assert_eq!(test_case.expected_reported, stream_data.is_author_spammer(test_case.author), "author: {}", test_case.author);
+            assert_eq!(test_case.expected_error, errored, "author: {}", test_case.author);
+        }
+    }
+
+    fn post_and_retract(author: &str, retraction_delay: u64) -> Vec<ChatAction> {
+        let mut actions = Vec::new();
+        for i in 0..3u64 {
+            let timestamp = (i + 1) * 60_000_000;
+            actions.push(ChatAction::Message {
+                id: format!("{}_{}", author, i),
+                author: author.to_string(),
+                timestamp,
+                content: format!("link {}", i),
+                badges: None,
+                context_params: String::new()
+            });
+            if i < 2 {
+                actions.push(ChatAction::RetractedMessage {
+                    author: author.to_string(),
+                    timestamp: timestamp + retraction_delay
+                });
+            }
+        }
+        actions
+    }
+
+    #[test]
+    pub fn test_retracted_messages() {
+        struct TestCase {
+            author: &'static str,
+            retraction_delay: u64,
+            expected_reported: bool
+        }
+
+        let mut reg_dates = HashMap::new();
+        reg_dates.insert("old".to_string(), Ok(Some(RegDate::new(2015, 1, 1))));
+        let loader = TestLoader { reg_dates };
+
+        // The rule is off by default
+        let mut cached_loader = CachedRegDateLoader::new(RegDate::default(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+        block_on(stream_data.process_messages(&DetectorParams::get_default(), &mut cached_loader, post_and_retract("quick", 1_000_000)));
+        assert!(!stream_data.is_author_spammer("quick"));
+
+        let params = DetectorParams::builder().retracted_message_count(2).build().unwrap();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(loader));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let test_cases = vec![
+            TestCase { author: "quick", retraction_delay: 1_000_000, expected_reported: true },
+            TestCase { author: "slow", retraction_delay: 30_000_000, expected_reported: false },
+            TestCase { author: "old", retraction_delay: 1_000_000, expected_reported: false },
+        ];
+
+        for test_case in test_cases {
+            let actions = post_and_retract(test_case.author, test_case.retraction_delay);
+            let results = block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+            let reported_messages: Vec<_> = results
+                .into_iter()
+                .filter_map(|r| r.ok())
+                .filter(|r| matches!(r.reason, Reason::RetractedMessage))
+                .map(|r| r.message_id)
+                .collect();
+
+            assert_eq!(test_case.expected_reported, stream_data.is_author_spammer(test_case.author), "author: {}", test_case.author);
+            if test_case.expected_reported {
+                assert_eq!(vec![format!("{}_2", test_case.author)], reported_messages);
+            } else {
+                assert!(reported_messages.is_empty(), "author: {}", test_case.author);
+            }
+        }
+    }
+
+    fn message(author: &str, timestamp: u64, content: &str) -> ChatAction {
+        ChatAction::Message {
+            id: format!("{}_{}", author, timestamp),
+            author: author.to_string(),
+            timestamp,
+            content: content.to_string(),
+            badges: None,
+            context_params: String::new()
+        }
+    }
+
+    #[test]
+    pub fn test_author_eviction() {
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        json["authorIdleTimeout"] = 60_000.into();
+        json["maxTrackedAuthors"] = 3.into();
+        json["maxMessagesPerAuthor"] = 2.into();
+        let params: DetectorParams = serde_json::from_value(json).unwrap();
+
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let actions = vec![
+            message("a", 1_000_000, "first message from a"),
+            message("a", 5_000_000, "totally different text"),
+            message("a", 9_000_000, "and one more unrelated line"),
+            message("b", 2_000_000, "hello"),
+            message("c", 3_000_000, "hello"),
+        ];
+        block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        let stats = stream_data.memory_stats();
+        assert_eq!(3, stats.tracked_authors);
+        assert_eq!(4, stats.stored_messages);
+        assert!(stats.estimated_bytes > 0);
+
+        // A new author pushes out the least recently active one, along with their report
+        let score = SpamScore::new(vec![Reason::Similar, Reason::TooFast(1_000)], &params).unwrap();
+        stream_data.authors_to_report.insert("b".to_string(), score);
+        stream_data.add_list_entry(ListEntry { author: "x".to_string(), list: ListKind::Deny, expires_at: None, note: String::new() }, &params);
+        block_on(stream_data.process_messages(&params, &mut cached_loader, vec![message("d", 10_000_000, "hi")]));
+        assert_eq!(3, stream_data.memory_stats().tracked_authors);
+        assert!(!stream_data.authors.contains_key("b"));
+        assert!(!stream_data.is_author_spammer("b"));
+        assert!(stream_data.is_author_spammer("x"));
+        assert_eq!(1, stream_data.memory_stats().reported_authors);
+
+        // Everyone except the newest author has been idle for too long
+        block_on(stream_data.process_messages(&params, &mut cached_loader, vec![message("e", 70_000_000, "hi")]));
+        let mut authors: Vec<&String> = stream_data.authors.keys().collect();
+        authors.sort();
+        assert_eq!(vec!["d", "e"], authors);
+    }
+
+    #[test]
+    pub fn test_reported_author_is_not_idle() {
+        let mut json = serde_json::to_value(DetectorParams::get_default()).unwrap();
+        json["authorIdleTimeout"] = 60_000.into();
+        json["maxTrackedAuthors"] = 3.into();
+        let params: DetectorParams = serde_json::from_value(json).unwrap();
+
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+        block_on(stream_data.process_messages(&params, &mut cached_loader, spam_from("spammer")));
+        assert!(stream_data.is_author_spammer("spammer"));
+
+        // The spammer keeps posting every 10 seconds while new authors come and go
+        for i in 1..=20u64 {
+            let timestamp = 30_000_000 + i * 10_000_000;
+            let actions = vec![
+                message("spammer", timestamp, "buy cheap followers here"),
+                message(&format!("viewer_{}", i), timestamp, "hello"),
+            ];
+            block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        }
+
+        assert!(stream_data.authors.contains_key("spammer"));
+        assert!(stream_data.is_author_spammer("spammer"));
+    }
+
+    #[test]
+    pub fn test_invalid_actions() {
+        let params = DetectorParams::get_default();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let actions = vec![
+            message("a", 10_000_000, "hello"),
+            message("a", 5_000_000, "from the past"),
+            message("", 11_000_000, "nobody"),
+        ];
+        let errors: Vec<DetectorError> = block_on(stream_data.process_messages(&params, &mut cached_loader, actions))
+            .into_iter()
+            .filter_map(|result| result.err())
+            .map(|error| error.error)
+            .collect();
+
+        assert_eq!(vec![
+            DetectorError::TimestampRegression { author: "a".to_string(), last_timestamp: 10_000, timestamp: 5_000 },
+            DetectorError::MalformedAction("message without id or author".to_string()),
+        ], errors);
+        assert!(errors.iter().all(|error| !error.is_retryable()));
+        assert!(DetectorError::LoaderFailure("timeout".to_string()).is_retryable());
+    }
+
+    #[test]
+    pub fn test_snapshot_restore() {
+        let params = DetectorParams::get_default();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+        stream_data.set_slow_mode(5_000);
+
+        let mut spam = spam_from("spammer");
+        let last_message = spam.pop().unwrap();
+        let mut actions = spam;
+        actions.push(ChatAction::Support { author: "patron".to_string(), timestamp: 25_000_000, amount: 500, currency: "USD".to_string() });
+        block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        assert!(!stream_data.is_author_spammer("spammer"));
+
+        let json = serde_json::to_string(&stream_data.snapshot()).unwrap();
+        let mut restored = StreamData::new(default_checks());
+        restored.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(stream_data.memory_stats(), restored.memory_stats());
+        assert_eq!(5_000, restored.slow_mode);
+        assert!(restored.superchated_authors.contains_key("patron"));
+
+        // Spammer's history survived the restart
+        block_on(restored.process_messages(&params, &mut cached_loader, vec![last_message]));
+        assert!(restored.is_author_spammer("spammer"));
+
+        let json = serde_json::to_string(&restored.snapshot()).unwrap();
+        let mut restored_twice = StreamData::new(default_checks());
+        restored_twice.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert!(restored_twice.is_author_spammer("spammer"));
+
+        // Snapshots from newer versions of the library are rejected, even when their shape changed
+        let mut json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        json["version"] = (SNAPSHOT_VERSION + 1).into();
+        json["authors"] = "moved elsewhere".into();
+        let error = serde_json::from_value::<Snapshot>(json).err().unwrap();
+        let expected = DetectorError::UnsupportedSnapshotVersion { version: SNAPSHOT_VERSION + 1, supported_version: SNAPSHOT_VERSION };
+        assert_eq!(expected.to_string(), error.to_string());
+
+        let mut snapshot = restored_twice.snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        assert_eq!(expected, restored_twice.restore(snapshot).unwrap_err());
+        assert!(restored_twice.is_author_spammer("spammer"));
+
+        // Formats that don't describe themselves work too
+        let bytes = bincode::serialize(&restored.snapshot()).unwrap();
+        let mut restored_from_bytes = StreamData::new(default_checks());
+        restored_from_bytes.restore(bincode::deserialize(&bytes).unwrap()).unwrap();
+        assert_eq!(restored.memory_stats(), restored_from_bytes.memory_stats());
+        assert!(restored_from_bytes.is_author_spammer("spammer"));
+        assert!(restored_from_bytes.superchated_authors.contains_key("patron"));
+
+        let mut snapshot = restored.snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        let mut bytes = bincode::serialize(&snapshot).unwrap();
+        bytes.truncate(16);
+        let error = bincode::deserialize::<Snapshot>(&bytes).err().unwrap();
+        assert_eq!(expected.to_string(), error.to_string());
+    }
+
+    struct RaidLoader {
+        batch_sizes: Arc<Mutex<Vec<usize>>>
+    }
+
+    #[async_trait]
+    impl RegDateLoader for RaidLoader {
+        async fn load(&self, _author: &str) -> Result<Option<RegDate>, DetectorError> {
+            Ok(Some(RegDate::new(2021, 1, 1)))
+        }
+
+        async fn load_many(&self, authors: &[String]) -> Vec<Result<Option<RegDate>, DetectorError>> {
+            self.batch_sizes.lock().unwrap().push(authors.len());
+            let mut results = Vec::new();
+            for author in authors {
+                results.push(self.load(author).await);
+            }
+            results
+        }
+    }
+
+    #[test]
+    pub fn test_raid_is_loaded_in_batches() {
+        let params = DetectorParams::get_default();
+        let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+        let loader = RaidLoader { batch_sizes: batch_sizes.clone() };
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(loader))
+            .with_batch_size(8);
+        let mut stream_data = StreamData::new(default_checks());
+
+        let actions: Vec<ChatAction> = (0..20)
+            .flat_map(|i| spam_from(&format!("bot{}", i)))
+            .collect();
+        let results = block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+
+        assert_eq!(20, stream_data.get_spammers().len());
+        assert!(results.iter().all(|result| result.is_ok()));
+        let mut batch_sizes = batch_sizes.lock().unwrap().clone();
+        batch_sizes.sort();
+        assert_eq!(vec![4, 8, 8], batch_sizes);
+    }
+
+    #[test]
+    pub fn test_reevaluate_authors() {
+        struct TestCase {
+            params: DetectorParams,
+            expected_flagged: Vec<&'static str>,
+            expected_unflagged: Vec<&'static str>,
+            expected_spammers: Vec<&'static str>
+        }
+
+        let mut reg_dates = HashMap::new();
+        reg_dates.insert("chatty".to_string(), Ok(Some(RegDate::new(2015, 1, 1))));
+        let mut cached_loader = CachedRegDateLoader::new(RegDate::default(), Box::new(TestLoader { reg_dates }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let params = DetectorParams::get_default();
+        let mut actions = spam_from("spammer");
+        actions.extend(spam_from("chatty"));
+        block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        assert_eq!(vec!["spammer"], stream_data.get_spammers().keys().collect::<Vec<_>>());
+
+        let test_cases = vec![
+            // Nothing changed
+            TestCase {
+                params: DetectorParams::get_default(),
+                expected_flagged: vec![],
+                expected_unflagged: vec![],
+                expected_spammers: vec!["spammer"]
+            },
+            // Repeating messages alone is enough to be flagged now
+            TestCase {
+                params: DetectorParams::builder().similar_weight(100).build().unwrap(),
+                expected_flagged: vec!["chatty"],
+                expected_unflagged: vec![],
+                expected_spammers: vec!["chatty", "spammer"]
+            },
+            // Nobody repeated themselves often enough
+            TestCase {
+                params: DetectorParams::builder().similarity_message_count(4).build().unwrap(),
+                expected_flagged: vec![],
+                expected_unflagged: vec!["chatty", "spammer"],
+                expected_spammers: vec![]
+            },
+        ];
+
+        for test_case in test_cases {
+            let diff = block_on(stream_data.reevaluate_authors(&test_case.params, &mut cached_loader));
+            let mut spammers: Vec<&String> = stream_data.get_spammers().keys().collect();
+            spammers.sort();
+
+            assert_eq!(test_case.expected_flagged, diff.flagged);
+            assert_eq!(test_case.expected_unflagged, diff.unflagged);
+            assert!(diff.errors.is_empty());
+            assert_eq!(test_case.expected_spammers, spammers);
+        }
+
+        block_on(stream_data.reevaluate_authors(&DetectorParams::get_default(), &mut cached_loader));
+        assert_eq!(vec!["spammer"], stream_data.clear_authors_to_report());
+        assert!(stream_data.get_spammers().is_empty());
+
+        // Only the repetitions of the last message count, so an author who moved on isn't flagged for what they repeated before
+        block_on(stream_data.process_messages(&params, &mut cached_loader, vec![message("chatty", 40_000_000, "what a great stream today")]));
+        let diff = block_on(stream_data.reevaluate_authors(&DetectorParams::builder().similar_weight(100).build().unwrap(), &mut cached_loader));
+        assert_eq!(vec!["spammer"], diff.flagged);
+    }
+
+    fn messages_with_badges(author: &str, count: u64, delay: u64, badges: Option<Vec<Badges>>) -> Vec<ChatAction> {
+        (0..count)
+            .map(|i| ChatAction::Message {
+                id: format!("{}_{}", author, i),
+                author: author.to_string(),
+                timestamp: (i + 1) * delay,
+                content: "buy cheap followers here".to_string(),
+                badges: badges.clone(),
+                context_params: String::new()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_badge_policies() {
+        struct TestCase {
+            author: &'static str,
+            badges: Option<Vec<Badges>>,
+            flooding: bool,
+            expected_reported: bool
+        }
+
+        let params = DetectorParams::get_default();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let test_cases = vec![
+            TestCase { author: "viewer", badges: None, flooding: false, expected_reported: true },
+            TestCase { author: "no_badges", badges: Some(vec![]), flooding: false, expected_reported: true },
+            TestCase { author: "member", badges: Some(vec![Badges::MEMBER]), flooding: false, expected_reported: false },
+            TestCase { author: "flooding_member", badges: Some(vec![Badges::MEMBER]), flooding: true, expected_reported: true },
+            TestCase { author: "moderator", badges: Some(vec![Badges::MODERATOR]), flooding: true, expected_reported: false },
+            TestCase { author: "member_moderator", badges: Some(vec![Badges::MEMBER, Badges::MODERATOR]), flooding: true, expected_reported: false },
+        ];
+
+        for test_case in test_cases {
+            let actions = if test_case.flooding {
+                messages_with_badges(test_case.author, 10, 200_000, test_case.badges)
+            } else {
+                messages_with_badges(test_case.author, 3, 10_000_000, test_case.badges)
+            };
+            block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+            assert_eq!(test_case.expected_reported, stream_data.is_author_spammer(test_case.author), "author: {}", test_case.author);
+        }
+
+        // Badges are remembered, so a new policy applies to authors who are already tracked
+        let params = DetectorParams::builder().member_policy(BadgePolicy::Exempt).build().unwrap();
+        let diff = block_on(stream_data.reevaluate_authors(&params, &mut cached_loader));
+        assert_eq!(vec!["flooding_member"], diff.unflagged);
+
+        let params = DetectorParams::builder().member_policy(BadgePolicy::Normal).build().unwrap();
+        let diff = block_on(stream_data.reevaluate_authors(&params, &mut cached_loader));
+        let mut flagged = diff.flagged;
+        flagged.sort();
+        assert_eq!(vec!["flooding_member", "member"], flagged);
+    }
+
+    fn support(author: &str, timestamp: u64, amount: u64) -> ChatAction {
+        support_in(author, timestamp, amount, "USD")
+    }
+
+    fn support_in(author: &str, timestamp: u64, amount: u64, currency: &str) -> ChatAction {
+        ChatAction::Support {
+            author: author.to_string(),
+            timestamp,
+            amount,
+            currency: currency.to_string()
+        }
+    }
+
+    #[test]
+    pub fn test_support_exemptions() {
+        struct TestCase {
+            params: DetectorParams,
+            amount: u64,
+            currency: &'static str,
+            expected_reported: bool
+        }
+
+        let test_cases = vec![
+            TestCase { params: DetectorParams::get_default(), amount: 100, currency: "USD", expected_reported: false },
+            TestCase { params: DetectorParams::get_default(), amount: 100, currency: "JPY", expected_reported: false },
+            TestCase { params: DetectorParams::builder().min_support_amount(500).build().unwrap(), amount: 100, currency: "USD", expected_reported: true },
+            TestCase { params: DetectorParams::builder().min_support_amount(500).build().unwrap(), amount: 500, currency: "USD", expected_reported: false },
+            // 500 yen are not 5 dollars
+            TestCase { params: DetectorParams::builder().min_support_amount(500).build().unwrap(), amount: 500, currency: "JPY", expected_reported: true },
+            TestCase {
+                params: DetectorParams::builder().min_support_amount(500).support_currency("JPY").build().unwrap(),
+                amount: 500,
+                currency: "JPY",
+                expected_reported: false
+            },
+            TestCase {
+                params: DetectorParams::builder().support_report_policy(SupportReportPolicy::Keep).build().unwrap(),
+                amount: 500,
+                currency: "USD",
+                expected_reported: true
+            },
+        ];
+
+        for test_case in test_cases {
+            let mut cached_loader = CachedRegDateLoader::new(test_case.params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+            let mut stream_data = StreamData::new(default_checks());
+
+            let mut actions = spam_from("spammer");
+            actions.push(support_in("spammer", 35_000_000, test_case.amount, test_case.currency));
+            actions.push(message("spammer", 40_000_000, "buy cheap followers here"));
+            let results = block_on(stream_data.process_messages(&test_case.params, &mut cached_loader, actions));
+            let last_message_reported = results
+                .iter()
+                .filter_map(|result| result.as_ref().ok())
+                .any(|result| result.message_id == "spammer_40000000");
+
+            assert_eq!(test_case.expected_reported, stream_data.is_author_spammer("spammer"), "amount: {} {}", test_case.amount, test_case.currency);
+            assert_eq!(test_case.expected_reported, last_message_reported, "amount: {} {}", test_case.amount, test_case.currency);
+        }
+
+        // With Keep, every later message of a reported supporter is reported, whether it's in the same batch or not
+        let params = DetectorParams::builder().support_report_policy(SupportReportPolicy::Keep).build().unwrap();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let mut actions = spam_from("spammer");
+        actions.push(support("spammer", 35_000_000, 500));
+        actions.push(message("spammer", 40_000_000, "thanks for the stream"));
+        let results = block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        assert!(results.iter().any(|result| result.as_ref().is_ok_and(|result| result.message_id == "spammer_40000000")));
+
+        let actions = vec![message("spammer", 50_000_000, "what a great stream"), message("viewer", 50_000_000, "hello")];
+        let results = block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        let reported: Vec<String> = results
+            .into_iter()
+            .filter_map(|result| result.ok())
+            .map(|result| result.message_id)
+            .collect();
+        assert_eq!(vec!["spammer_50000000".to_string()], reported);
+
+        // The exemption ends after a minute, and the spammer is caught again
+        let params = DetectorParams::builder().support_exemption_duration(60_000).build().unwrap();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+        let mut stream_data = StreamData::new(default_checks());
+
+        let mut actions = vec![support("spammer", 1_000_000, 100)];
+        actions.extend(spam_from("spammer"));
+        block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        assert!(!stream_data.is_author_spammer("spammer"));
+        assert_eq!(1, stream_data.memory_stats().superchated_authors);
+
+        let actions = (7..10u64)
+            .map(|i| message("spammer", i * 10_000_000, "buy cheap followers here"))
+            .collect();
+        block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+        assert!(stream_data.is_author_spammer("spammer"));
+        assert_eq!(0, stream_data.memory_stats().superchated_authors);
+
+        // Supporters from snapshots written before exemptions could expire stay exempt
+        let mut json = serde_json::to_value(StreamData::new(default_checks()).snapshot()).unwrap();
+        json["version"] = 1.into();
+        json["superchatedAuthors"] = serde_json::json!(["patron"]);
+        let mut restored = StreamData::new(default_checks());
+        restored.restore(serde_json::from_value(json).unwrap()).unwrap();
+        assert!(restored.is_supporter("patron", u64::MAX));
+    }
+
+    fn list_entry(author: &str, list: ListKind, expires_at: Option<u64>) -> ListEntry {
+        ListEntry { author: author.to_string(), list, expires_at, note: String::new() }
+    }
+
+    #[test]
+    pub fn test_author_lists() {
+        struct TestCase {
+            author: &'static str,
+            badges: Option<Vec<Badges>>,
+            entry: Option<ListEntry>,
+            expected_reported: bool,
+            expected_reason: Option<Reason>
+        }
+
+        let params = DetectorParams::get_default();
+        let mut cached_loader = CachedRegDateLoader::new(params.min_reg_date_copy(), Box::new(TestLoader { reg_dates: HashMap::new() }));
+
+        let test_cases = vec![
+            TestCase { author: "spammer", badges: None, entry: None, expected_reported: true, expected_reason: Some(Reason::Similar) },
+            TestCase { author: "trusted", badges: None, entry: Some(list_entry("trusted", ListKind::Allow, None)), expected_reported: false, expected_reason: None },
+            TestCase {
+                author: "known_bot",
+                badges: Some(vec![Badges::MODERATOR]),
+                entry: Some(list_entry("known_bot", ListKind::Deny, None)),
+                expected_reported: true,
+                expected_reason: Some(Reason::Denylisted)
+            },
+            // Entries expire by the message's timestamp
+            TestCase { author: "forgiven", badges: None, entry: Some(list_entry("forgiven", ListKind::Allow, Some(5_000_000))), expected_reported: true, expected_reason: Some(Reason::Similar) },
+        ];
+
+        for test_case in test_cases {
+            let mut stream_data = StreamData::new(default_checks());
+            if let Some(entry) = test_case.entry {
+                stream_data.add_list_entry(entry, &params);
+            }
+
+            let actions = messages_with_badges(test_case.author, 3, 10_000_000, test_case.badges);
+            let results = block_on(stream_data.process_messages(&params, &mut cached_loader, actions));
+            let reasons: Vec<Reason> = results
+                .into_iter()
+                .filter_map(|result| result.ok())
+                .map(|result| result.reason)
+                .collect();
+
+            assert_eq!(test_case.expected_reported, stream_data.is_author_spammer(test_case.author), "author: {}", test_case.author);
+            assert_eq!(
+                test_case.expected_reason.map(|reason| format!("{:?}", reason)),
+                reasons.last().map(|reason| format!("{:?}", reason)),
+                "author: {}", test_case.author
+            );
+        }
+
+        // Denied authors are reported right away, and only lose the report the deny list gave them
+        let mut stream_data = StreamData::new(default_checks());
+        block_on(stream_data.process_messages(&params, &mut cached_loader, spam_from("spammer")));
+        let verdict_before_denial = stream_data.authors_to_report["spammer"].verdict;
+        assert!(!matches!(verdict_before_denial, Verdict::Ban));
+        stream_data.add_list_entry(list_entry("spammer", ListKind::Deny, None), &params);
+        stream_data.add_list_entry(list_entry("bot", ListKind::Deny, Some(100_000_000)), &params);
+        assert!(stream_data.is_author_spammer("bot"));
+        // Denying an author who was already reported bans them
+        assert!(matches!(stream_data.authors_to_report["spammer"].verdict, Verdict::Ban));
+        assert_eq!(Some(ListKind::Deny), stream_data.remove_list_entry("spammer", &params).map(|entry| entry.list));
+        assert!(stream_data.is_author_spammer("spammer"));
+        assert_eq!(verdict_before_denial, stream_data.authors_to_report["spammer"].verdict);
+
+        // Lists are part of the snapshot
+        let json = serde_json::to_string(&stream_data.snapshot()).unwrap();
+        let mut restored = StreamData::new(default_checks());
+        restored.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(stream_data.author_lists(), restored.author_lists());
+
+        // Allowing an author removes their report, and expired denials are dropped with their reports
+        restored.add_list_entry(list_entry("spammer", ListKind::Allow, None), &params);
+        assert!(!restored.is_author_spammer("spammer"));
+        block_on(restored.process_messages(&params, &mut cached_loader, vec![message("viewer", 200_000_000, "hello")]));
+        assert!(!restored.is_author_spammer("bot"));
+        assert_eq!(None, restored.author_lists().get("bot"));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Author who supported the stream and is not checked until the exemption ends.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupportExemption {
+    /// When the author supported the stream
+    #[serde(default)]
+    pub since: u64,
+    /// `None` if the exemption never ends
+    pub expires_at: Option<u64>,
+    /// The last support, in minor units of `currency`
+    pub amount: u64,
+    pub currency: String
+}
+
+impl SupportExemption {
+    pub fn new(since: u64, expires_at: Option<u64>, amount: u64, currency: String) -> Self {
+        SupportExemption {
+            since,
+            expires_at,
+            amount,
+            currency
+        }
+    }
+
+    pub fn is_active(&self, timestamp: u64) -> bool {
+        self.since <= timestamp && !self.is_expired(timestamp)
+    }
+
+    pub fn is_expired(&self, timestamp: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| timestamp > expires_at)
+    }
+
+    /// Takes another support from the same author into account, keeping the later end of the two.
+    /// A support after the exemption ended starts a new one.
+    pub fn extend(&mut self, other: SupportExemption) {
+        if !self.is_active(other.since) {
+            self.since = other.since;
+        }
+        self.expires_at = match (self.expires_at, other.expires_at) {
+            (Some(current), Some(other)) => Some(current.max(other)),
+            _ => None
+        };
+        self.amount = other.amount;
+        self.currency = other.currency;
+    }
+
+    pub fn estimated_size(&self) -> usize {
+        std::mem::size_of::<SupportExemption>() + self.currency.len()
+    }
+}
+
+/// Snapshots before version 2 only stored the names of authors, who were exempt forever.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredExemptions {
+    Legacy(HashSet<String>),
+    Current(HashMap<String, SupportExemption>)
+}
+
+pub fn deserialize_exemptions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, SupportExemption>, D::Error> {
+    Ok(match StoredExemptions::deserialize(deserializer)? {
+        StoredExemptions::Legacy(authors) => legacy_exemptions(authors),
+        StoredExemptions::Current(exemptions) => exemptions
+    })
+}
+
+/// Exemptions of the authors stored by snapshots before version 2.
+pub fn legacy_exemptions(authors: HashSet<String>) -> HashMap<String, SupportExemption> {
+    authors
+        .into_iter()
+        .map(|author| (author, SupportExemption::new(0, None, 0, String::new())))
+        .collect()
+}